@@ -0,0 +1,463 @@
+//! Image registration module for Glassvis application.
+
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::corners::Corner;
+use imageproc::filter::gaussian_blur_f32;
+use imageproc::geometric_transformations::{warp_into, Interpolation, Projection};
+
+use crate::error::{Error, Result};
+use crate::imgfx;
+
+/// Half size of the square patch sampled by descriptors.
+const PATCH_RADIUS: i32 = 15;
+
+/// Maximum number of corners kept per image.
+const MAX_CORNERS: usize = 500;
+
+/// Number of random samples drawn by RANSAC.
+const RANSAC_ITERATIONS: usize = 1000;
+
+/// Maximum reprojection error (in pixels) of an inlier match.
+const INLIER_THRESHOLD: f64 = 3.0;
+
+/// Minimum number of inliers required to accept a transform.
+const MIN_INLIERS: usize = 8;
+
+/// Represents an estimated capture-to-reference transform.
+pub struct Alignment {
+    pub matrix: [f32; 9],
+    pub residual: f32,
+    pub inliers: usize,
+    pub matches: usize,
+}
+
+/// Represents a (captured point, reference point) match.
+type Match = ((f64, f64), (f64, f64));
+
+/// Represents a 256-bit binary descriptor of a corner.
+struct Feature {
+    x: f64,
+    y: f64,
+    bits: [u64; 4],
+}
+
+/// Simple linear congruential generator for repeatable sampling.
+struct Lcg(u64);
+
+impl Lcg {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        return self.0 >> 33;
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        return (self.next() % n as u64) as usize;
+    }
+}
+
+/// Returns the fixed point-pair layout shared by all descriptors.
+fn sample_pairs() -> Vec<(i32, i32, i32, i32)> {
+    let mut rng = Lcg(0x5eed);
+    let span = (2 * PATCH_RADIUS + 1) as usize;
+    let mut coord = || rng.below(span) as i32 - PATCH_RADIUS;
+    let mut pairs = Vec::with_capacity(256);
+
+    for _ in 0..256 {
+        pairs.push((coord(), coord(), coord(), coord()));
+    }
+
+    return pairs;
+}
+
+/// Detects FAST corners, keeping the strongest ones away from image borders.
+fn detect(src: &GrayImage) -> Vec<Corner> {
+    let (width, height) = src.dimensions();
+    let margin = PATCH_RADIUS as u32 + 1;
+
+    let mut corners = imgfx::detect_corners_f9(src, 24);
+    if corners.len() < MAX_CORNERS / 10 {
+        corners = imgfx::detect_corners_f12(src, 12);
+    }
+
+    corners.retain(|c| {
+        c.x >= margin && c.y >= margin && c.x + margin < width && c.y + margin < height
+    });
+    corners.sort_by(|a, b| b.score.total_cmp(&a.score));
+    corners.truncate(MAX_CORNERS);
+
+    return corners;
+}
+
+/// Computes BRIEF-style binary descriptors for corners of a smoothed image.
+fn describe(src: &GrayImage, corners: &Vec<Corner>, pairs: &Vec<(i32, i32, i32, i32)>) -> Vec<Feature> {
+    let smooth = gaussian_blur_f32(src, 2.0);
+    let mut features = Vec::with_capacity(corners.len());
+
+    for corner in corners.iter() {
+        let cx = corner.x as i32;
+        let cy = corner.y as i32;
+        let mut bits = [0u64; 4];
+
+        for (i, &(x1, y1, x2, y2)) in pairs.iter().enumerate() {
+            let p = smooth.get_pixel((cx + x1) as u32, (cy + y1) as u32)[0];
+            let q = smooth.get_pixel((cx + x2) as u32, (cy + y2) as u32)[0];
+            if p < q {
+                bits[i / 64] |= 1 << (i % 64);
+            }
+        }
+
+        features.push(Feature{ x: corner.x as f64, y: corner.y as f64, bits: bits });
+    }
+
+    return features;
+}
+
+/// Returns the hamming distance between two descriptors.
+fn hamming(a: &[u64; 4], b: &[u64; 4]) -> u32 {
+    let mut dist = 0;
+    for i in 0..4 {
+        dist += (a[i] ^ b[i]).count_ones();
+    }
+    return dist;
+}
+
+/// Matches captured features to reference features using a ratio test.
+/// Returns (captured point, reference point) pairs.
+fn match_features(src: &Vec<Feature>, dst: &Vec<Feature>) -> Vec<Match> {
+    let mut matches = Vec::new();
+
+    for q in dst.iter() {
+        let mut best = (u32::MAX, 0);
+        let mut second = u32::MAX;
+
+        for (i, p) in src.iter().enumerate() {
+            let dist = hamming(&p.bits, &q.bits);
+            if dist < best.0 {
+                second = best.0;
+                best = (dist, i);
+            } else if dist < second {
+                second = dist;
+            }
+        }
+
+        // keep distinctive matches only
+        if best.0 < 64 && (best.0 as f64) < 0.8 * second as f64 {
+            let p = &src[best.1];
+            matches.push(((q.x, q.y), (p.x, p.y)));
+        }
+    }
+
+    return matches;
+}
+
+/// Solves a dense linear system using gaussian elimination with partial pivoting.
+fn solve(mut a: Vec<Vec<f64>>, mut b: Vec<f64>) -> Option<Vec<f64>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+            .unwrap();
+        // singular or degenerate (NaN) systems have no solution
+        if a[pivot][col].abs() < 1e-12 || a[pivot][col].is_nan() {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col].clone();
+
+        for row in (col + 1)..n {
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot_value) in a[row][col..n].iter_mut().zip(pivot_row[col..n].iter()) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let mut sum = b[row];
+        for k in (row + 1)..n {
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+
+    return Some(x);
+}
+
+/// Returns the similarity transform that centers points and scales them to unit spread.
+fn normalizer(points: &Vec<(f64, f64)>) -> [f64; 9] {
+    let n = points.len() as f64;
+    let cx = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let cy = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let spread = points.iter().map(|p| ((p.0 - cx).powi(2) + (p.1 - cy).powi(2)).sqrt()).sum::<f64>() / n;
+    let s = if spread > 0.0 { 2f64.sqrt() / spread } else { 1.0 };
+
+    return [s, 0.0, -s * cx, 0.0, s, -s * cy, 0.0, 0.0, 1.0];
+}
+
+/// Multiplies two 3x3 row-major matrices.
+fn mul(a: &[f64; 9], b: &[f64; 9]) -> [f64; 9] {
+    let mut c = [0.0; 9];
+    for r in 0..3 {
+        for k in 0..3 {
+            c[r * 3 + k] = a[r * 3] * b[k] + a[r * 3 + 1] * b[3 + k] + a[r * 3 + 2] * b[6 + k];
+        }
+    }
+    return c;
+}
+
+/// Applies a homography to a point.
+fn project(h: &[f64; 9], p: (f64, f64)) -> (f64, f64) {
+    let w = h[6] * p.0 + h[7] * p.1 + h[8];
+    let x = (h[0] * p.0 + h[1] * p.1 + h[2]) / w;
+    let y = (h[3] * p.0 + h[4] * p.1 + h[5]) / w;
+    return (x, y);
+}
+
+/// Fits a homography to point pairs in the least-squares sense.
+fn fit_homography(pairs: &Vec<Match>) -> Option<[f64; 9]> {
+    let from: Vec<(f64, f64)> = pairs.iter().map(|m| m.0).collect();
+    let to: Vec<(f64, f64)> = pairs.iter().map(|m| m.1).collect();
+    let t1 = normalizer(&from);
+    let t2 = normalizer(&to);
+
+    // accumulate normal equations of the DLT system with h33 = 1
+    let mut ata = vec![vec![0.0; 8]; 8];
+    let mut atb = vec![0.0; 8];
+
+    for i in 0..pairs.len() {
+        let (x, y) = project(&t1, from[i]);
+        let (u, v) = project(&t2, to[i]);
+        let rows = [
+            ([x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y], u),
+            ([0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y], v),
+        ];
+
+        for (row, rhs) in rows.iter() {
+            for j in 0..8 {
+                for k in 0..8 {
+                    ata[j][k] += row[j] * row[k];
+                }
+                atb[j] += row[j] * rhs;
+            }
+        }
+    }
+
+    let h = solve(ata, atb)?;
+    let hn = [h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0];
+
+    // undo normalization: H = T2^-1 * Hn * T1
+    let s = t2[0];
+    let t2_inv = [1.0 / s, 0.0, -t2[2] / s, 0.0, 1.0 / s, -t2[5] / s, 0.0, 0.0, 1.0];
+    let mut m = mul(&t2_inv, &mul(&hn, &t1));
+
+    if m[8].abs() < 1e-12 {
+        return None;
+    }
+    let scale = m[8];
+    for v in m.iter_mut() {
+        *v /= scale;
+    }
+
+    return Some(m);
+}
+
+/// Returns the reprojection error of a point pair under a homography.
+fn reproj_error(h: &[f64; 9], pair: &Match) -> f64 {
+    let (x, y) = project(h, pair.0);
+    return ((x - (pair.1).0).powi(2) + (y - (pair.1).1).powi(2)).sqrt();
+}
+
+/// Estimates a robust homography from point matches using RANSAC.
+fn ransac(matches: &Vec<Match>) -> Option<([f64; 9], Vec<usize>)> {
+    if matches.len() < 4 {
+        return None;
+    }
+
+    let mut rng = Lcg(0x9e3779b97f4a7c15);
+    let mut best: Vec<usize> = Vec::new();
+
+    for _ in 0..RANSAC_ITERATIONS {
+        let mut sample: Vec<usize> = Vec::with_capacity(4);
+        while sample.len() < 4 {
+            let i = rng.below(matches.len());
+            if !sample.contains(&i) {
+                sample.push(i);
+            }
+        }
+
+        let pairs = sample.iter().map(|&i| matches[i]).collect();
+        let h = match fit_homography(&pairs) {
+            Some(h) => h,
+            None    => continue,
+        };
+
+        let inliers: Vec<usize> = (0..matches.len())
+            .filter(|&i| reproj_error(&h, &matches[i]) < INLIER_THRESHOLD)
+            .collect();
+
+        if inliers.len() > best.len() {
+            best = inliers;
+        }
+    }
+
+    if best.len() < MIN_INLIERS {
+        return None;
+    }
+
+    // refine the model using all inliers
+    let pairs = best.iter().map(|&i| matches[i]).collect();
+    let h = fit_homography(&pairs)?;
+
+    return Some((h, best));
+}
+
+/// Estimates the homography mapping the captured image onto the reference image.
+pub fn estimate(src: &GrayImage, dst: &GrayImage) -> Option<Alignment> {
+    let pairs = sample_pairs();
+
+    let src_features = describe(src, &detect(src), &pairs);
+    let dst_features = describe(dst, &detect(dst), &pairs);
+
+    let matches = match_features(&src_features, &dst_features);
+    let (h, inliers) = ransac(&matches)?;
+
+    // root mean square reprojection error of inlier matches
+    let sum: f64 = inliers.iter().map(|&i| reproj_error(&h, &matches[i]).powi(2)).sum();
+    let residual = (sum / inliers.len() as f64).sqrt();
+
+    let mut matrix = [0f32; 9];
+    for i in 0..9 {
+        matrix[i] = h[i] as f32;
+    }
+
+    let alignment = Alignment{
+        matrix: matrix,
+        residual: residual as f32,
+        inliers: inliers.len(),
+        matches: matches.len(),
+    };
+
+    return Some(alignment);
+}
+
+/// Warps the captured image onto the reference image frame. Returns the warped image
+/// and a coverage mask that is set where the captured image covers the reference frame.
+/// Uncovered areas are filled from the reference and must be left out of the inspection.
+pub fn warp_onto(src: &DynamicImage, dst: &DynamicImage, alignment: &Alignment) -> Option<(DynamicImage, GrayImage)> {
+    let projection = Projection::from_matrix(alignment.matrix)?;
+    let reference = src.to_rgba8();
+    let (width, height) = src.dimensions();
+
+    let mut out = RgbaImage::new(width, height);
+    let mut coverage = GrayImage::new(width, height);
+    warp_into(&dst.to_rgba8(), &projection, Interpolation::Bilinear, Rgba([0, 0, 0, 0]), &mut out);
+
+    for (x, y, pix) in out.enumerate_pixels_mut() {
+        if pix[3] < 255 {
+            *pix = *reference.get_pixel(x, y);
+        } else {
+            coverage.put_pixel(x, y, Luma([255u8]));
+        }
+    }
+
+    return Some((DynamicImage::ImageRgba8(out), coverage));
+}
+
+/// Aligns the captured image to the reference image.
+/// Returns the warped image, its coverage mask and the estimated transform.
+pub fn align(src: &DynamicImage, dst: &DynamicImage) -> Result<(DynamicImage, GrayImage, Alignment)> {
+    let alignment = estimate(&src.to_luma8(), &dst.to_luma8())
+        .ok_or(Error::Alignment(String::from("no consistent transform found between the images")))?;
+    let (warped, coverage) = warp_onto(src, dst, &alignment)
+        .ok_or(Error::Alignment(String::from("the estimated transform is not invertible")))?;
+
+    return Ok((warped, coverage, alignment));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rotates a point by an angle (in degrees) and translates it.
+    fn rigid(p: (f64, f64), angle: f64, tx: f64, ty: f64) -> (f64, f64) {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        return (cos * p.0 - sin * p.1 + tx, sin * p.0 + cos * p.1 + ty);
+    }
+
+    /// Returns scattered points of a 400x300 image.
+    fn points(count: usize) -> Vec<(f64, f64)> {
+        let mut rng = Lcg(42);
+        return (0..count).map(|_| (rng.below(400) as f64, rng.below(300) as f64)).collect();
+    }
+
+    /// Renders a blocky random texture with plenty of corners.
+    fn texture(width: u32, height: u32) -> GrayImage {
+        let mut rng = Lcg(7);
+        let cells: Vec<u8> = (0..(width / 8 + 1) * (height / 8 + 1)).map(|_| rng.below(256) as u8).collect();
+        return GrayImage::from_fn(width, height, |x, y| Luma([cells[((y / 8) * (width / 8 + 1) + x / 8) as usize]]));
+    }
+
+    #[test]
+    fn ransac_recovers_translation_and_rotation() {
+        let mut matches: Vec<Match> = points(60).into_iter().map(|p| (p, rigid(p, 5.0, 12.0, -7.0))).collect();
+
+        // mismatched pairs must be rejected as outliers
+        let mut rng = Lcg(1);
+        for (p, q) in matches.iter_mut().take(15) {
+            *q = (p.0 + 40.0 + rng.below(80) as f64, p.1 - 30.0 - rng.below(60) as f64);
+        }
+
+        let (h, inliers) = ransac(&matches).unwrap();
+        assert_eq!(inliers.len(), 45);
+        assert!(inliers.iter().all(|&i| i >= 15));
+
+        for &(x, y) in [(0.0, 0.0), (400.0, 0.0), (200.0, 150.0), (0.0, 300.0)].iter() {
+            let (u, v) = project(&h, (x, y));
+            let (eu, ev) = rigid((x, y), 5.0, 12.0, -7.0);
+            assert!((u - eu).abs() < 1e-6 && (v - ev).abs() < 1e-6, "({}, {}) != ({}, {})", u, v, eu, ev);
+        }
+    }
+
+    #[test]
+    fn ransac_needs_enough_matches() {
+        let matches: Vec<Match> = points(3).into_iter().map(|p| (p, p)).collect();
+        assert!(ransac(&matches).is_none());
+    }
+
+    #[test]
+    fn estimate_recovers_image_shift() {
+        let src = texture(240, 200);
+        let dst = GrayImage::from_fn(240, 200, |x, y| {
+            if x >= 6 && y >= 4 {
+                return *src.get_pixel(x - 6, y - 4);
+            }
+            return Luma([0u8]);
+        });
+
+        // the captured image is shifted by (6, 4), so it maps back by (-6, -4)
+        let alignment = estimate(&src, &dst).unwrap();
+        let m = alignment.matrix;
+        assert!((m[2] + 6.0).abs() < 0.5 && (m[5] + 4.0).abs() < 0.5, "{:?}", m);
+        assert!((m[0] - 1.0).abs() < 0.01 && (m[4] - 1.0).abs() < 0.01, "{:?}", m);
+    }
+
+    #[test]
+    fn warp_marks_uncovered_pixels() {
+        let src = DynamicImage::ImageLuma8(texture(64, 48));
+        let alignment = Alignment{
+            matrix: [1.0, 0.0, 10.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+            residual: 0.0,
+            inliers: 0,
+            matches: 0,
+        };
+
+        let (_, coverage) = warp_onto(&src, &src, &alignment).unwrap();
+        assert_eq!(coverage.get_pixel(5, 20)[0], 0);
+        assert_eq!(coverage.get_pixel(30, 20)[0], 255);
+    }
+}
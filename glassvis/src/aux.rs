@@ -0,0 +1,100 @@
+//! Auxiliary module for Glassvis.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::string::String;
+
+use crate::error::{Error, Result};
+
+/// Returns "data" directory path (as path buffer).
+pub fn get_path() -> PathBuf {
+    let path = PathBuf::from("data");
+    return path;
+}
+
+/// Returns "data" directory path (as path buffer).
+pub fn set_path(prefix: &str, dst: &str) -> Result<PathBuf> {
+    let file_name = prefix.to_owned() + &get_filename(&dst.to_string())?;
+
+    let mut path = get_path();
+    path.push("output");
+    path.push(file_name);
+
+    return Ok(path);
+}
+
+/// Returns a prefixed file path in the directory of given file (e.g. "diff_capt.png" beside "capt.png").
+pub fn set_path_beside(prefix: &str, dst: &str) -> Result<PathBuf> {
+    let file_name = prefix.to_owned() + &get_filename(&dst.to_string())?;
+    let path = to_path(&dst.to_string()).with_file_name(file_name);
+
+    return Ok(path);
+}
+
+/// Converts given 'String' to 'Path'.
+pub fn to_path(input: &String) -> &Path {
+    let output = Path::new(input);
+
+    return output;
+}
+
+/// Extracts the filename (as String) from a given path.
+pub fn get_filename(file_path: &String) -> Result<String> {
+    let file_name = to_path(file_path)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(Error::Missing(format!("file name in '{}'", file_path)))?;
+
+    return Ok(file_name.to_string());
+}
+
+/// Extensions of the supported image file formats.
+const IMAGE_EXTENSIONS: [&str; 13] = [
+    "bmp", "gif", "jpg", "jpeg", "png", "pbm", "pgm", "ppm", "pnm", "tga", "tif", "tiff", "webp",
+];
+
+/// Checks if input file is an image, by its extension (case-insensitive).
+pub fn is_image_file(input: &str) -> bool {
+    let ext = match Path::new(input).extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => ext,
+        None => return false,
+    };
+
+    return IMAGE_EXTENSIONS.iter().any(|known| known.eq_ignore_ascii_case(ext));
+}
+
+/// Lists the image files of a directory in file name order.
+pub fn list_images(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && is_image_file(&path.to_string_lossy()) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+
+    return Ok(files);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_image_extensions_ignoring_case() {
+        assert!(is_image_file("capt.png"));
+        assert!(is_image_file("data/capture/CAPT.JPG"));
+        assert!(is_image_file("scan.Tiff"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(is_image_file("notes.png.txt") == false);
+        assert!(is_image_file("capt.png~") == false);
+        assert!(is_image_file("data/png") == false);
+        assert!(is_image_file("archive.jpgs") == false);
+    }
+}
@@ -0,0 +1,305 @@
+//! Batch module for Glassvis.
+//!
+//! A batch inspects all image files of a directory with one recipe, either against
+//! a single reference image or against the reference mapped to each capture by a
+//! CSV file of `capture,reference` lines (capture file names, reference paths
+//! relative to the mapping file):
+//!
+//! ```text
+//! capture,reference
+//! panel_001.png,ref/model_a.png
+//! panel_002.png,ref/model_b.png
+//! ```
+//!
+//! Captures that can not be inspected (e.g. corrupt files) are listed as failures
+//! instead of stopping the batch. The results are collected into a summary table
+//! of file, defect count, defect rate and verdict.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+
+use crate::aux;
+use crate::error::{Error, Result};
+use crate::recipe::Recipe;
+use crate::report::{csv_field, Report};
+use crate::verdict::{self, Verdict};
+use crate::Inspection;
+
+/// Header row of the CSV batch summary.
+const CSV_HEADER: &str = "file,defects,defect_rate,verdict,error\n";
+
+/// Represents the reference images of the captures of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum References {
+    /// One reference image for all captures.
+    Single(String),
+    /// Reference image of each capture, by capture file name.
+    Mapping(HashMap<String, String>),
+}
+
+impl References {
+    /// Opens a reference image path, or a reference mapping if the path is a ".csv" file.
+    pub fn open(path: &str) -> Result<References> {
+        let is_mapping = Path::new(path).extension().map(|ext| ext == "csv").unwrap_or(false);
+
+        if is_mapping == false {
+            return Ok(References::Single(path.to_string()));
+        }
+
+        let text = fs::read_to_string(path)?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut mapping = HashMap::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            // skip blank lines, comments and the header row
+            if line.is_empty() || line.starts_with('#') || line == "capture,reference" {
+                continue;
+            }
+
+            let mut fields = line.splitn(2, ',').map(|field| field.trim().trim_matches('"'));
+
+            match (fields.next(), fields.next()) {
+                (Some(capture), Some(reference)) if capture.is_empty() == false && reference.is_empty() == false => {
+                    let reference = dir.join(reference).to_string_lossy().to_string();
+                    mapping.insert(capture.to_string(), reference);
+                }
+                _ => return Err(Error::Batch(format!("invalid mapping at '{}' line {}", path, i + 1))),
+            }
+        }
+
+        return Ok(References::Mapping(mapping));
+    }
+
+    /// Returns the reference image path of a capture.
+    pub fn get(&self, capture: &Path) -> Result<String> {
+        let mapping = match self {
+            References::Single(path) => return Ok(path.clone()),
+            References::Mapping(mapping) => mapping,
+        };
+
+        let file_name = capture.file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let reference = mapping.get(&file_name)
+            .ok_or(Error::Missing(format!("reference image of '{}' in mapping", file_name)))?;
+
+        return Ok(reference.clone());
+    }
+}
+
+/// Represents the captures of a batch along with their references.
+pub struct Batch {
+    /// References of the captures (none = hotspot detection).
+    pub references: Option<References>,
+    /// Captured image files in file name order.
+    pub captures: Vec<PathBuf>,
+    /// Last decoded reference image along with its path.
+    cache: Option<(String, DynamicImage)>,
+}
+
+impl Batch {
+    /// Lists the image files of a directory as captures of a batch.
+    pub fn open(references: Option<References>, dir: &Path) -> Result<Batch> {
+        let captures = aux::list_images(dir)?;
+
+        if captures.is_empty() {
+            return Err(Error::Missing(format!("image files in '{}'", dir.display())));
+        }
+
+        let batch = Batch{
+            references: references,
+            captures: captures,
+            cache: None,
+        };
+
+        return Ok(batch);
+    }
+
+    /// Returns the decoded reference image at a path, keeping it for the next capture.
+    fn reference(&mut self, path: &str) -> Result<&DynamicImage> {
+        let stale = match &self.cache {
+            Some((cached, _)) => cached != path,
+            None => true,
+        };
+
+        if stale {
+            // free the previous image before decoding the next one
+            self.cache = None;
+            self.cache = Some((path.to_string(), crate::open(path)?));
+        }
+
+        match &self.cache {
+            Some((_, src)) => return Ok(src),
+            None => return Err(Error::Missing(format!("reference image at '{}'", path))),
+        }
+    }
+
+    /// Inspects a capture with a recipe. Returns the inspection along with its report.
+    pub fn inspect(&mut self, capture: &Path, recipe: &Recipe) -> Result<(Inspection, Report)> {
+        let capture_path = capture.to_string_lossy().to_string();
+        let settings = &recipe.settings;
+        let dst = crate::open(&capture_path)?;
+
+        let reference = match &self.references {
+            Some(references) => Some(references.get(capture)?),
+            None => None,
+        };
+
+        let inspection = match &reference {
+            Some(path) => crate::compare(self.reference(path)?, &dst, settings)?,
+            None => crate::detect(&dst, settings)?,
+        };
+
+        let judgement = verdict::judge(&inspection, settings, &recipe.limits);
+        let report = Report::new(&inspection, settings, reference.as_deref(), &capture_path, &judgement)?;
+
+        return Ok((inspection, report));
+    }
+}
+
+/// Represents the result of an inspected capture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+    pub file: String,
+    pub defect_count: u32,
+    pub defect_rate: f32,
+    pub verdict: Verdict,
+}
+
+/// Represents a capture that could not be inspected.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    pub file: String,
+    pub error: String,
+}
+
+/// Represents the summary of a batch.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Summary {
+    pub rows: Vec<Row>,
+    pub failures: Vec<Failure>,
+}
+
+impl Summary {
+    /// Adds the result of an inspected capture.
+    pub fn add(&mut self, file: &str, report: &Report) {
+        self.rows.push(Row{
+            file: file.to_string(),
+            defect_count: report.defect_count,
+            defect_rate: report.defect_rate,
+            verdict: report.verdict,
+        });
+    }
+
+    /// Adds a capture that could not be inspected.
+    pub fn fail(&mut self, file: &str, err: &Error) {
+        self.failures.push(Failure{ file: file.to_string(), error: err.to_string() });
+    }
+
+    /// Returns the number of captures with a verdict.
+    pub fn count(&self, verdict: Verdict) -> usize {
+        return self.rows.iter().filter(|row| row.verdict == verdict).count();
+    }
+
+    /// Returns the worst verdict of the inspected captures.
+    pub fn verdict(&self) -> Option<Verdict> {
+        return self.rows.iter().map(|row| row.verdict).max();
+    }
+
+    /// Formats the summary as CSV, one row per capture, failures last.
+    pub fn to_csv(&self) -> String {
+        let mut text = String::from(CSV_HEADER);
+
+        for row in self.rows.iter() {
+            text.push_str(&format!("{},{},{:.4},{},\n",
+                csv_field(&row.file), row.defect_count, row.defect_rate, row.verdict.name()));
+        }
+
+        for failure in self.failures.iter() {
+            text.push_str(&format!("{},,,ERROR,{}\n", csv_field(&failure.file), csv_field(&failure.error)));
+        }
+
+        return text;
+    }
+
+    /// Saves the summary to a CSV file.
+    pub fn save_csv(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, self.to_csv())?;
+
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a reference mapping into a fresh temp directory and opens it.
+    fn open_mapping(name: &str, text: &str) -> (PathBuf, Result<References>) {
+        let dir = std::env::temp_dir().join(format!("glassvis-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("mapping.csv");
+        fs::write(&path, text).unwrap();
+        let references = References::open(path.to_str().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        return (dir, references);
+    }
+
+    #[test]
+    fn opens_single_reference() {
+        assert_eq!(References::open("ref/model_a.png").unwrap(), References::Single(String::from("ref/model_a.png")));
+    }
+
+    #[test]
+    fn parses_mapping() {
+        let text = "capture,reference\n\n# model A\npanel_001.png, ref/model_a.png\n\"panel_002.png\",\"ref/model_b.png\"\n";
+        let (dir, references) = open_mapping("mapping", text);
+        let references = references.unwrap();
+
+        let model_a = dir.join("ref/model_a.png").to_string_lossy().to_string();
+        let model_b = dir.join("ref/model_b.png").to_string_lossy().to_string();
+        assert_eq!(references.get(Path::new("/captures/panel_001.png")).unwrap(), model_a);
+        assert_eq!(references.get(Path::new("panel_002.png")).unwrap(), model_b);
+        assert!(matches!(references.get(Path::new("panel_003.png")), Err(Error::Missing(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_mapping_lines() {
+        let (_, references) = open_mapping("invalid", "capture,reference\npanel_001.png,ref/a.png\npanel_002.png\n");
+
+        match references {
+            Err(Error::Batch(message)) => assert!(message.ends_with("line 3"), "{}", message),
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+
+        let (_, references) = open_mapping("empty-field", "panel_001.png,\n");
+        assert!(matches!(references, Err(Error::Batch(_))));
+    }
+
+    #[test]
+    fn lists_failures_last() {
+        let mut summary = Summary::default();
+        summary.rows.push(Row{ file: String::from("a,1.png"), defect_count: 2, defect_rate: 0.5, verdict: Verdict::Fail });
+        summary.fail("b.png", &Error::Missing(String::from("reference")));
+
+        let text = summary.to_csv();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "\"a,1.png\",2,0.5000,FAIL,");
+        assert!(lines[2].starts_with("b.png,,,ERROR,"));
+        assert_eq!(summary.verdict(), Some(Verdict::Fail));
+    }
+}
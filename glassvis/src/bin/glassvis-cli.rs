@@ -0,0 +1,529 @@
+//! Headless command-line front end for Glassvis inspections.
+
+use std::env::args;
+use std::fs;
+use std::path::Path;
+use std::process::exit;
+
+use image::GenericImageView;
+
+use glassvis::report::Images;
+use glassvis::{
+    imgfx, source, stack, verdict, Batch, DefectClass, History, Recipe, References, Report, Result, Summary, Verdict,
+};
+
+/// Exit status of a panel that passed inspection.
+const EXIT_PASS: i32 = 0;
+
+/// Exit status of a panel that failed inspection.
+const EXIT_FAIL: i32 = 1;
+
+/// Exit status of a panel marked for review.
+const EXIT_REVIEW: i32 = 4;
+
+/// Exit status of invalid invocations.
+const EXIT_USAGE: i32 = 2;
+
+/// Exit status of inspections that could not be completed.
+const EXIT_ERROR: i32 = 3;
+
+/// Represents parsed options of the compare and batch commands.
+/// The batch command takes a reference image or mapping and a capture directory as paths.
+struct Options {
+    ref_path: String,
+    capt_path: String,
+    recipe: Recipe,
+    out: Option<String>,
+    json: Option<String>,
+    pdf: Option<String>,
+    html: Option<String>,
+    csv: Option<String>,
+    append: bool,
+    history: Option<String>,
+    summary: Option<String>,
+}
+
+/// Prints usage info.
+fn usage() {
+    eprintln!("Usage: glassvis-cli compare <ref> <capt> [options]");
+    eprintln!("       glassvis-cli batch <ref|mapping.csv> <dir> [options]");
+    eprintln!("       glassvis-cli grab <source> <out> [--frames N] [--merge METHOD]");
+    eprintln!("       glassvis-cli merge <out> <file>... [--merge METHOD]");
+    eprintln!();
+    eprintln!("Sources: v4l2:/dev/video0, file:PATH, dir:PATH, synthetic[:defects,noise=N,size=WxH]");
+    eprintln!("Mapping: CSV file of capture,reference lines (capture file name, reference path)");
+    eprintln!();
+    eprintln!("Options:");
+    eprintln!("  --recipe FILE      load settings from a TOML or JSON recipe (other options override it)");
+    eprintln!("  --significance N   defect significance (1-100, default 10)");
+    eprintln!("  --mode MODE        luma, ssim, de76, de94 or de2000 (default luma)");
+    eprintln!("  --delta-e T        color difference threshold (default 2.3)");
+    eprintln!("  --ssim-radius R    SSIM window radius in pixels (default 3)");
+    eprintln!("  --morph OP         none, erode, dilate, open or close (default none)");
+    eprintln!("  --kernel K         square or diamond (default square)");
+    eprintln!("  --radius R         structuring element radius (default 1)");
+    eprintln!("  --min-area A       minimum defect area in pixels (default 1)");
+    eprintln!("  --mask FILE        binary mask image of inspected pixels (white = inspect)");
+    eprintln!("  --max-rate R       maximum defect rate in percent to pass (default unlimited)");
+    eprintln!("  --max-count C=N    maximum number of defects of a class (e.g. scratch=0)");
+    eprintln!("  --max-area A       maximum area of a single defect in pixels");
+    eprintln!("  --forbid ZONE      fail on any defect inside a zone");
+    eprintln!("  --review F         mark panels above this fraction of a limit for review (default 1 = never)");
+    eprintln!("  --align            align captured image to reference first");
+    eprintln!("  --bbox, --no-bbox  draw a bounding box around each defect (default on)");
+    eprintln!("  --out FILE         save marked diff image to file");
+    eprintln!("  --json FILE        save inspection result as JSON");
+    eprintln!("  --pdf FILE         save printable PDF inspection report");
+    eprintln!("  --html FILE        save self-contained HTML inspection report");
+    eprintln!("  --csv FILE         save defect list as CSV, one row per defect");
+    eprintln!("  --append           append to the CSV file instead of overwriting it");
+    eprintln!("  --history FILE     record the inspection in a history database (e.g. data/history.db)");
+    eprintln!();
+    eprintln!("Batch options:");
+    eprintln!("  --out DIR          save marked diff images into a directory");
+    eprintln!("  --csv FILE         save the defect lists of all captures into one CSV file");
+    eprintln!("  --summary FILE     save the summary table as CSV");
+    eprintln!();
+    eprintln!("Grab and merge options:");
+    eprintln!("  --frames N         grab N consecutive frames and merge them (default 1)");
+    eprintln!("  --merge METHOD     mean, median or sigma (sigma-clipped mean, default mean)");
+}
+
+/// Returns the value following an option or exits with usage info.
+fn value(argv: &Vec<String>, i: usize) -> String {
+    match argv.get(i + 1) {
+        Some(val) => val.clone(),
+        None => {
+            eprintln!("Missing value for {}!", argv[i]);
+            usage();
+            exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Parses a numeric option value or exits with usage info.
+fn number<T: std::str::FromStr>(argv: &Vec<String>, i: usize) -> T {
+    match value(argv, i).parse::<T>() {
+        Ok(num) => num,
+        Err(_) => {
+            eprintln!("Invalid value for {}!", argv[i]);
+            exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Parses an option value with given identifier parser or exits with usage info,
+/// since the parsers fall back to a default on unknown identifiers.
+fn choice<T>(argv: &Vec<String>, i: usize, from_id: fn(&str) -> T, id: fn(&T) -> &'static str) -> T {
+    let val = value(argv, i);
+    let parsed = from_id(&val);
+
+    if id(&parsed) != val {
+        eprintln!("Invalid value for {}!", argv[i]);
+        exit(EXIT_USAGE);
+    }
+
+    return parsed;
+}
+
+/// Parses a class limit (CLASS=N) option value or exits with usage info.
+fn class_limit(argv: &Vec<String>, i: usize) -> (String, u32) {
+    let val = value(argv, i);
+    let mut parts = val.splitn(2, '=');
+    let class = parts.next().unwrap_or_default();
+    let max = parts.next().and_then(|max| max.parse::<u32>().ok());
+
+    match (DefectClass::from_id(class), max) {
+        (Some(class), Some(max)) => (class.id().to_string(), max),
+        _ => {
+            eprintln!("Invalid value for {}!", argv[i]);
+            exit(EXIT_USAGE);
+        }
+    }
+}
+
+/// Loads the recipe given with the --recipe option, or the default recipe.
+fn load_recipe(argv: &Vec<String>) -> Recipe {
+    let i = match argv.iter().position(|arg| arg == "--recipe") {
+        Some(i) => i,
+        None => return Recipe::default(),
+    };
+
+    match Recipe::load(&value(argv, i)) {
+        Ok(recipe) => recipe,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Parses the arguments of the compare command.
+fn parse(argv: &Vec<String>) -> Options {
+    let mut paths = Vec::new();
+    let mut opts = Options{
+        ref_path: String::new(),
+        capt_path: String::new(),
+        recipe: load_recipe(argv),
+        out: None,
+        json: None,
+        pdf: None,
+        html: None,
+        csv: None,
+        append: false,
+        history: None,
+        summary: None,
+    };
+
+    let settings = &mut opts.recipe.settings;
+    let limits = &mut opts.recipe.limits;
+
+    let mut i = 0;
+    while i < argv.len() {
+        match argv[i].as_str() {
+            "--recipe"       => i += 1,
+            "--significance" => { settings.significance = number(argv, i); i += 1; }
+            "--mode"         => { settings.mode = choice(argv, i, imgfx::DiffMode::from_id, imgfx::DiffMode::id); i += 1; }
+            "--delta-e"      => { settings.delta_e = number(argv, i); i += 1; }
+            "--ssim-radius"  => { settings.ssim_radius = number(argv, i); i += 1; }
+            "--morph"        => { settings.filter.op = choice(argv, i, imgfx::MorphOp::from_id, imgfx::MorphOp::id); i += 1; }
+            "--kernel"       => { settings.filter.kernel = choice(argv, i, imgfx::Kernel::from_id, imgfx::Kernel::id); i += 1; }
+            "--radius"       => { settings.filter.radius = number(argv, i); i += 1; }
+            "--min-area"     => { settings.filter.min_area = number(argv, i); i += 1; }
+            "--mask"         => { settings.roi.mask = Some(value(argv, i)); i += 1; }
+            "--max-rate"     => { limits.max_rate = Some(number(argv, i)); i += 1; }
+            "--max-count"    => { let (class, max) = class_limit(argv, i); limits.max_count.insert(class, max); i += 1; }
+            "--max-area"     => { limits.max_area = Some(number(argv, i)); i += 1; }
+            "--forbid"       => { limits.forbidden_zones.push(value(argv, i)); i += 1; }
+            "--review"       => { limits.review = number(argv, i); i += 1; }
+            "--out"          => { opts.out = Some(value(argv, i)); i += 1; }
+            "--json"         => { opts.json = Some(value(argv, i)); i += 1; }
+            "--pdf"          => { opts.pdf = Some(value(argv, i)); i += 1; }
+            "--html"         => { opts.html = Some(value(argv, i)); i += 1; }
+            "--csv"          => { opts.csv = Some(value(argv, i)); i += 1; }
+            "--append"       => opts.append = true,
+            "--history"      => { opts.history = Some(value(argv, i)); i += 1; }
+            "--summary"      => { opts.summary = Some(value(argv, i)); i += 1; }
+            "--align"        => settings.align = true,
+            "--bbox"         => opts.recipe.output.bbox = true,
+            "--no-bbox"      => opts.recipe.output.bbox = false,
+            arg if arg.starts_with("--") => {
+                eprintln!("Unknown option {}!", arg);
+                usage();
+                exit(EXIT_USAGE);
+            }
+            arg => paths.push(arg.to_string()),
+        }
+        i += 1;
+    }
+
+    if paths.len() != 2 || opts.recipe.settings.significance == 0 {
+        usage();
+        exit(EXIT_USAGE);
+    }
+
+    if let Err(err) = opts.recipe.limits.validate() {
+        eprintln!("Error: {}", err);
+        exit(EXIT_USAGE);
+    }
+
+    opts.ref_path = paths[0].clone();
+    opts.capt_path = paths[1].clone();
+
+    return opts;
+}
+
+/// Runs the compare command and returns the exit status.
+fn compare(opts: &Options) -> Result<i32> {
+    let settings = &opts.recipe.settings;
+    let src = glassvis::open(&opts.ref_path)?;
+    let dst = glassvis::open(&opts.capt_path)?;
+
+    let inspection = glassvis::compare(&src, &dst, settings)?;
+    let defects = &inspection.defects;
+    let defect_rate = inspection.defect_rate();
+
+    if settings.align {
+        match &inspection.alignment {
+            Some(alignment) => {
+                println!("Alignment: H = {:?}  RMS = {:.2}px  ({}/{} inliers)",
+                    alignment.matrix, alignment.residual, alignment.inliers, alignment.matches);
+            }
+            None => {
+                let reason = inspection.alignment_error.as_deref().unwrap_or("no transform");
+                println!("Alignment: failed ({})", reason);
+            }
+        }
+    }
+
+    if let Some(index) = inspection.score {
+        println!("SSIM: {:.4}", index);
+    }
+
+    println!("Total Defect Rate: {}%", defect_rate);
+    println!("Defects: {}", defects.len());
+
+    for (class, count) in inspection.class_counts() {
+        println!("  {}: {}", class.name(), count);
+    }
+
+    println!("{:>5} {:>6} {:>6} {:>6} {:>6} {:>8} {:>9} {:>9} {:>8} {:>4} {:>6} {:>6} {:>6} {:>6}  {:<10} zone",
+        "id", "x", "y", "width", "height", "area", "cx", "cy", "mean", "max",
+        "elong", "circ", "solid", "angle", "class");
+
+    for defect in defects.iter() {
+        let f = &defect.features;
+        println!("{:>5} {:>6} {:>6} {:>6} {:>6} {:>8} {:>9.2} {:>9.2} {:>8.2} {:>4} {:>6.2} {:>6.2} {:>6.2} {:>6.1}  {:<10} {}",
+            defect.id, defect.rect.left(), defect.rect.top(), defect.rect.width(), defect.rect.height(),
+            defect.area, defect.centroid.0, defect.centroid.1, defect.mean_contrast, defect.max_contrast,
+            f.elongation, f.circularity, f.solidity, f.orientation, defect.class.name(),
+            defect.zone.as_deref().unwrap_or("-"));
+    }
+
+    for grade in inspection.grades.iter() {
+        let mark = if grade.passed() { "pass" } else { "fail" };
+        println!("Zone {}: {} defects ({} allowed), {:.4}% of {} px  {}",
+            grade.name, grade.defects, grade.max_defects,
+            grade.counter as f32 * 100.0 / grade.area.max(1) as f32, grade.area, mark);
+    }
+
+    let diff = glassvis::annotate(&inspection, &opts.recipe.output)?;
+
+    if let Some(out) = &opts.out {
+        glassvis::save(&diff, out)?;
+    }
+
+    let judgement = verdict::judge(&inspection, settings, &opts.recipe.limits);
+
+    if opts.json.is_some() || opts.pdf.is_some() || opts.html.is_some() || opts.csv.is_some() || opts.history.is_some() {
+        let report = Report::new(&inspection, settings, Some(&opts.ref_path), &opts.capt_path, &judgement)?;
+        let images = Images{
+            reference: Some(&src),
+            capture: &inspection.image,
+            annotated: &diff,
+        };
+
+        if let Some(json) = &opts.json {
+            report.save_json(json)?;
+        }
+
+        if let Some(pdf) = &opts.pdf {
+            report.save_pdf(&images, pdf)?;
+        }
+
+        if let Some(html) = &opts.html {
+            report.save_html(&images, html)?;
+        }
+
+        match &opts.csv {
+            Some(csv) if opts.append => report.append_csv(csv)?,
+            Some(csv) => report.save_csv(csv)?,
+            None => (),
+        }
+
+        if let Some(history) = &opts.history {
+            let image = opts.out.clone().unwrap_or_default();
+            History::open(Path::new(history))?.record(&report, &opts.recipe.name, &image)?;
+        }
+    }
+
+    println!("Verdict: {}", judgement.verdict.name());
+
+    for finding in judgement.findings.iter() {
+        println!("  {}: {}", finding.verdict.name(), finding.rule);
+    }
+
+    let status = match judgement.verdict {
+        Verdict::Pass   => EXIT_PASS,
+        Verdict::Review => EXIT_REVIEW,
+        Verdict::Fail   => EXIT_FAIL,
+    };
+
+    return Ok(status);
+}
+
+/// Inspects a capture of a batch, saving its marked image, defect list and history record as requested.
+fn batch_capture(batch: &mut Batch, capture: &Path, opts: &Options, history: &mut Option<History>) -> Result<Report> {
+    let (inspection, report) = batch.inspect(capture, &opts.recipe)?;
+    let mut image = String::new();
+
+    if let Some(out) = &opts.out {
+        let file_name = capture.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        image = Path::new(out).join(format!("diff_{}", file_name)).to_string_lossy().to_string();
+
+        let diff = glassvis::annotate(&inspection, &opts.recipe.output)?;
+        glassvis::save(&diff, &image)?;
+    }
+
+    if let Some(csv) = &opts.csv {
+        report.append_csv(csv)?;
+    }
+
+    if let Some(history) = history {
+        history.record(&report, &opts.recipe.name, &image)?;
+    }
+
+    return Ok(report);
+}
+
+/// Prints the summary table of a batch.
+fn print_summary(summary: &Summary) {
+    println!("{:<40} {:>8} {:>10}  verdict", "file", "defects", "rate");
+
+    for row in summary.rows.iter() {
+        println!("{:<40} {:>8} {:>9.4}%  {}", row.file, row.defect_count, row.defect_rate, row.verdict.name());
+    }
+
+    for failure in summary.failures.iter() {
+        println!("{:<40} {:>8} {:>10}  ERROR: {}", failure.file, "-", "-", failure.error);
+    }
+
+    println!("Captures: {}  PASS: {}  REVIEW: {}  FAIL: {}  Errors: {}",
+        summary.rows.len() + summary.failures.len(), summary.count(Verdict::Pass),
+        summary.count(Verdict::Review), summary.count(Verdict::Fail), summary.failures.len());
+}
+
+/// Runs the batch command over a directory of captures and returns the exit status.
+fn batch(opts: &Options) -> Result<i32> {
+    if opts.json.is_some() || opts.pdf.is_some() || opts.html.is_some() {
+        eprintln!("The batch command does not support --json, --pdf and --html!");
+        return Ok(EXIT_USAGE);
+    }
+
+    let references = References::open(&opts.ref_path)?;
+    let mut batch = Batch::open(Some(references), Path::new(&opts.capt_path))?;
+
+    let mut history = match &opts.history {
+        Some(path) => Some(History::open(Path::new(path))?),
+        None => None,
+    };
+
+    // the defect lists of all captures are appended to one file
+    if let Some(csv) = &opts.csv {
+        if opts.append == false && Path::new(csv).exists() {
+            fs::remove_file(csv)?;
+        }
+    }
+
+    let captures = batch.captures.clone();
+    let mut summary = Summary::default();
+
+    for (i, capture) in captures.iter().enumerate() {
+        let file = capture.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        eprintln!("[{}/{}] {}", i + 1, captures.len(), file);
+
+        // a capture that can not be inspected does not stop the batch
+        match batch_capture(&mut batch, capture, opts, &mut history) {
+            Ok(report) => summary.add(&file, &report),
+            Err(err) => summary.fail(&file, &err),
+        }
+    }
+
+    print_summary(&summary);
+
+    if let Some(path) = &opts.summary {
+        summary.save_csv(path)?;
+    }
+
+    if summary.failures.is_empty() == false {
+        return Ok(EXIT_ERROR);
+    }
+
+    let status = match summary.verdict() {
+        Some(Verdict::Fail)   => EXIT_FAIL,
+        Some(Verdict::Review) => EXIT_REVIEW,
+        _                     => EXIT_PASS,
+    };
+
+    return Ok(status);
+}
+
+/// Parses the arguments of the grab and merge commands into paths, frame count and merge method.
+fn parse_stack(argv: &Vec<String>) -> (Vec<String>, usize, stack::Method) {
+    let mut paths = Vec::new();
+    let mut count = 1;
+    let mut method = stack::Method::Mean;
+
+    let mut i = 0;
+    while i < argv.len() {
+        match argv[i].as_str() {
+            "--frames" => { count = number(argv, i); i += 1; }
+            "--merge"  => { method = choice(argv, i, stack::Method::from_id, stack::Method::id); i += 1; }
+            arg if arg.starts_with("--") => {
+                eprintln!("Unknown option {}!", arg);
+                usage();
+                exit(EXIT_USAGE);
+            }
+            arg => paths.push(arg.to_string()),
+        }
+        i += 1;
+    }
+
+    return (paths, count, method);
+}
+
+/// Runs the grab command, saving one (merged) frame of a frame source, and returns the exit status.
+fn grab(argv: &Vec<String>) -> Result<i32> {
+    let (paths, count, method) = parse_stack(argv);
+
+    if paths.len() != 2 || count == 0 {
+        usage();
+        return Ok(EXIT_USAGE);
+    }
+
+    let mut source = source::open(&paths[0])?;
+    let frame = stack::grab(source.as_mut(), count, method)?;
+    glassvis::save(&frame, &paths[1])?;
+
+    if count > 1 {
+        println!("Grabbed {} frames from {} merged into {}x{} frame ({})",
+            count, source.name(), frame.width(), frame.height(), method.id());
+    } else {
+        println!("Grabbed {}x{} frame from {}", frame.width(), frame.height(), source.name());
+    }
+
+    return Ok(EXIT_PASS);
+}
+
+/// Runs the merge command, saving image files merged into one frame, and returns the exit status.
+fn merge(argv: &Vec<String>) -> Result<i32> {
+    let (paths, _, method) = parse_stack(argv);
+
+    if paths.len() < 2 {
+        usage();
+        return Ok(EXIT_USAGE);
+    }
+
+    let frame = stack::open(&paths[1..], method)?;
+    glassvis::save(&frame, &paths[0])?;
+
+    println!("Merged {} files into {}x{} frame ({})", paths.len() - 1, frame.width(), frame.height(), method.id());
+
+    return Ok(EXIT_PASS);
+}
+
+/// Runs the command-line application.
+fn main() {
+    let argv: Vec<String> = args().skip(1).collect();
+
+    let result = match argv.first().map(|cmd| cmd.as_str()) {
+        Some("compare") => compare(&parse(&argv[1..].to_vec())),
+        Some("batch")   => batch(&parse(&argv[1..].to_vec())),
+        Some("grab")    => grab(&argv[1..].to_vec()),
+        Some("merge")   => merge(&argv[1..].to_vec()),
+        _ => {
+            usage();
+            Ok(EXIT_USAGE)
+        }
+    };
+
+    let status = match result {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            EXIT_ERROR
+        }
+    };
+
+    exit(status);
+}
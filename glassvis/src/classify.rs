@@ -0,0 +1,322 @@
+//! Defect classification module for Glassvis.
+//!
+//! Segmented defects are classified by their shape and contrast:
+//! edge chips touch the image border, scratches are elongated, smears are large
+//! and faint, bubbles (and seeds) are round and solid, and everything else is a spot.
+
+use image::{ImageBuffer, Luma, Rgba};
+use serde::{Deserialize, Serialize};
+
+/// Minimum ratio of the principal axes of a scratch.
+const SCRATCH_ELONGATION: f32 = 4.0;
+
+/// Minimum area of a smear in pixels.
+const SMEAR_AREA: u32 = 150;
+
+/// Maximum mean contrast of a smear.
+const SMEAR_CONTRAST: f32 = 40.0;
+
+/// Minimum area of a bubble in pixels (smaller round defects are spots).
+const BUBBLE_AREA: u32 = 9;
+
+/// Minimum circularity of a bubble.
+const BUBBLE_CIRCULARITY: f32 = 0.75;
+
+/// Minimum solidity of a bubble.
+const BUBBLE_SOLIDITY: f32 = 0.85;
+
+/// Represents the classes of defects.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DefectClass {
+    Scratch,
+    Bubble,
+    Spot,
+    Smear,
+    EdgeChip,
+}
+
+impl DefectClass {
+    /// All defect classes in display order.
+    pub const ALL: [DefectClass; 5] = [
+        DefectClass::Scratch,
+        DefectClass::Bubble,
+        DefectClass::Spot,
+        DefectClass::Smear,
+        DefectClass::EdgeChip,
+    ];
+
+    /// Parses a defect class from its identifier.
+    pub fn from_id(id: &str) -> Option<DefectClass> {
+        let class = match id {
+            "scratch"   => DefectClass::Scratch,
+            "bubble"    => DefectClass::Bubble,
+            "spot"      => DefectClass::Spot,
+            "smear"     => DefectClass::Smear,
+            "edge-chip" => DefectClass::EdgeChip,
+            _           => return None,
+        };
+
+        return Some(class);
+    }
+
+    /// Returns the identifier of a defect class (as used in recipes and results).
+    pub fn id(&self) -> &'static str {
+        let id = match self {
+            DefectClass::Scratch  => "scratch",
+            DefectClass::Bubble   => "bubble",
+            DefectClass::Spot     => "spot",
+            DefectClass::Smear    => "smear",
+            DefectClass::EdgeChip => "edge-chip",
+        };
+
+        return id;
+    }
+
+    /// Returns the display name of a defect class.
+    pub fn name(&self) -> &'static str {
+        let name = match self {
+            DefectClass::Scratch  => "scratch",
+            DefectClass::Bubble   => "bubble",
+            DefectClass::Spot     => "spot",
+            DefectClass::Smear    => "smear",
+            DefectClass::EdgeChip => "edge chip",
+        };
+
+        return name;
+    }
+
+    /// Returns the bounding box color of a defect class.
+    pub fn color(&self) -> Rgba<u8> {
+        let color = match self {
+            DefectClass::Scratch  => Rgba([255, 128, 0, 0]),
+            DefectClass::Bubble   => Rgba([0, 200, 255, 0]),
+            DefectClass::Spot     => Rgba([0, 255, 0, 0]),
+            DefectClass::Smear    => Rgba([200, 0, 255, 0]),
+            DefectClass::EdgeChip => Rgba([255, 255, 0, 0]),
+        };
+
+        return color;
+    }
+}
+
+/// Represents the geometric features of a defect region.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    /// Ratio of the major to the minor principal axis (1 for compact regions).
+    pub elongation: f32,
+    /// Isoperimetric ratio 4πA/P² (1 for disks).
+    pub circularity: f32,
+    /// Ratio of the area to the convex hull area (1 for convex regions).
+    pub solidity: f32,
+    /// Angle of the major axis in degrees, counter-clockwise from the x axis.
+    pub orientation: f32,
+}
+
+/// Computes the features of a labelled region from its pixels.
+pub fn measure(pixels: &Vec<(u32, u32)>, labels: &ImageBuffer<Luma<u32>, Vec<u32>>) -> Features {
+    let area = pixels.len() as f32;
+    if pixels.is_empty() {
+        return Features::default();
+    }
+
+    let (width, height) = labels.dimensions();
+    let (x0, y0) = pixels[0];
+    let id = labels.get_pixel(x0, y0)[0];
+
+    // central second moments, widened by the variance of a unit pixel
+    let cx = pixels.iter().map(|p| p.0 as f32).sum::<f32>() / area;
+    let cy = pixels.iter().map(|p| p.1 as f32).sum::<f32>() / area;
+    let mut mu20 = 1.0 / 12.0;
+    let mut mu02 = 1.0 / 12.0;
+    let mut mu11 = 0.0;
+
+    for &(x, y) in pixels.iter() {
+        let dx = x as f32 - cx;
+        let dy = y as f32 - cy;
+        mu20 += dx * dx / area;
+        mu02 += dy * dy / area;
+        mu11 += dx * dy / area;
+    }
+
+    let common = ((mu20 - mu02) * (mu20 - mu02) + 4.0 * mu11 * mu11).sqrt();
+    let major = (mu20 + mu02 + common) / 2.0;
+    let minor = (mu20 + mu02 - common) / 2.0;
+
+    // count pixel edges exposed to the background and collect boundary corners
+    let mut edges = 0;
+    let mut corners: Vec<(i64, i64)> = Vec::new();
+    let inside = |x: i64, y: i64| {
+        x >= 0 && y >= 0 && x < width as i64 && y < height as i64 && labels.get_pixel(x as u32, y as u32)[0] == id
+    };
+
+    for &(x, y) in pixels.iter() {
+        let (x, y) = (x as i64, y as i64);
+        let exposed = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+            .iter()
+            .filter(|&&(nx, ny)| inside(nx, ny) == false)
+            .count();
+
+        if exposed > 0 {
+            edges += exposed;
+            corners.extend_from_slice(&[(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)]);
+        }
+    }
+
+    // staircase perimeter overestimates smooth outlines by 4/π
+    let perimeter = edges as f32 * std::f32::consts::PI / 4.0;
+    let circularity = 4.0 * std::f32::consts::PI * area / (perimeter * perimeter);
+    let hull = hull_area(&mut corners);
+
+    let features = Features{
+        elongation: (major / minor.max(1e-6)).sqrt(),
+        circularity: circularity.min(1.0),
+        solidity: if hull > 0.0 { (area / hull).min(1.0) } else { 1.0 },
+        orientation: -0.5 * (2.0 * mu11).atan2(mu20 - mu02).to_degrees(),
+    };
+
+    return features;
+}
+
+/// Computes the area of the convex hull of given points (monotone chain).
+fn hull_area(points: &mut Vec<(i64, i64)>) -> f32 {
+    points.sort();
+    points.dedup();
+
+    if points.len() < 3 {
+        return 0.0;
+    }
+
+    let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+
+    let mut hull: Vec<(i64, i64)> = Vec::new();
+
+    // lower hull, then upper hull
+    for pass in 0..2 {
+        let start = hull.len();
+        let iter: Vec<(i64, i64)> = if pass == 0 {
+            points.clone()
+        } else {
+            points.iter().rev().cloned().collect()
+        };
+
+        for p in iter {
+            while hull.len() >= start + 2 && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0 {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+
+        hull.pop();
+    }
+
+    // shoelace formula
+    let mut twice = 0;
+    for i in 0..hull.len() {
+        let a = hull[i];
+        let b = hull[(i + 1) % hull.len()];
+        twice += a.0 * b.1 - b.0 * a.1;
+    }
+
+    return twice.abs() as f32 / 2.0;
+}
+
+/// Classifies a defect by its features, size, contrast and position.
+pub fn classify(features: &Features, area: u32, mean_contrast: f32, on_border: bool) -> DefectClass {
+    if on_border {
+        return DefectClass::EdgeChip;
+    }
+
+    if features.elongation >= SCRATCH_ELONGATION {
+        return DefectClass::Scratch;
+    }
+
+    if area >= SMEAR_AREA && mean_contrast < SMEAR_CONTRAST {
+        return DefectClass::Smear;
+    }
+
+    let round = features.circularity >= BUBBLE_CIRCULARITY && features.solidity >= BUBBLE_SOLIDITY;
+    if area >= BUBBLE_AREA && round {
+        return DefectClass::Bubble;
+    }
+
+    return DefectClass::Spot;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Features of a compact, round and convex region.
+    const ROUND: Features = Features{ elongation: 1.0, circularity: 1.0, solidity: 1.0, orientation: 0.0 };
+
+    /// Measures a region made of given pixels in a label image of given size.
+    fn measure_region(pixels: Vec<(u32, u32)>, width: u32, height: u32) -> Features {
+        let mut labels = ImageBuffer::new(width, height);
+        for &(x, y) in pixels.iter() {
+            labels.put_pixel(x, y, Luma([1u32]));
+        }
+
+        return measure(&pixels, &labels);
+    }
+
+    #[test]
+    fn border_wins_over_shape() {
+        let long = Features{ elongation: 10.0, ..ROUND };
+        assert_eq!(classify(&long, 500, 10.0, true), DefectClass::EdgeChip);
+    }
+
+    #[test]
+    fn scratch_elongation_boundary() {
+        let at = Features{ elongation: SCRATCH_ELONGATION, ..ROUND };
+        let below = Features{ elongation: SCRATCH_ELONGATION - 0.01, ..ROUND };
+
+        assert_eq!(classify(&at, 20, 100.0, false), DefectClass::Scratch);
+        assert_eq!(classify(&below, 20, 100.0, false), DefectClass::Bubble);
+    }
+
+    #[test]
+    fn smear_area_and_contrast_boundaries() {
+        assert_eq!(classify(&ROUND, SMEAR_AREA, SMEAR_CONTRAST - 0.1, false), DefectClass::Smear);
+        assert_eq!(classify(&ROUND, SMEAR_AREA - 1, SMEAR_CONTRAST - 0.1, false), DefectClass::Bubble);
+        assert_eq!(classify(&ROUND, SMEAR_AREA, SMEAR_CONTRAST, false), DefectClass::Bubble);
+    }
+
+    #[test]
+    fn bubble_boundaries() {
+        let jagged = Features{ solidity: BUBBLE_SOLIDITY - 0.01, ..ROUND };
+        let angular = Features{ circularity: BUBBLE_CIRCULARITY - 0.01, ..ROUND };
+        let edge = Features{ circularity: BUBBLE_CIRCULARITY, solidity: BUBBLE_SOLIDITY, ..ROUND };
+
+        assert_eq!(classify(&edge, BUBBLE_AREA, 100.0, false), DefectClass::Bubble);
+        assert_eq!(classify(&ROUND, BUBBLE_AREA - 1, 100.0, false), DefectClass::Spot);
+        assert_eq!(classify(&jagged, 20, 100.0, false), DefectClass::Spot);
+        assert_eq!(classify(&angular, 20, 100.0, false), DefectClass::Spot);
+    }
+
+    #[test]
+    fn measures_disk_and_line() {
+        let disk: Vec<(u32, u32)> = (0..21u32)
+            .flat_map(|y| (0..21u32).map(move |x| (x, y)))
+            .filter(|&(x, y)| (x as i32 - 10).pow(2) + (y as i32 - 10).pow(2) <= 64)
+            .collect();
+        let disk = measure_region(disk, 21, 21);
+        assert!(disk.elongation < 1.05, "{:?}", disk);
+        assert!(disk.circularity > BUBBLE_CIRCULARITY, "{:?}", disk);
+        assert!(disk.solidity > BUBBLE_SOLIDITY, "{:?}", disk);
+
+        let line = measure_region((2..30).map(|x| (x, 5)).collect(), 32, 10);
+        assert!(line.elongation >= SCRATCH_ELONGATION, "{:?}", line);
+        assert!(line.orientation.abs() < 1.0, "{:?}", line);
+    }
+
+    #[test]
+    fn class_ids_round_trip() {
+        for class in DefectClass::ALL.iter() {
+            assert_eq!(DefectClass::from_id(class.id()), Some(*class));
+        }
+        assert_eq!(DefectClass::from_id("crack"), None);
+    }
+}
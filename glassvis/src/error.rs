@@ -0,0 +1,99 @@
+//! Error module for Glassvis.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::result;
+
+use image::ImageError;
+use rusqlite;
+
+/// Represents failures of the inspection pipeline.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// An image could not be decoded or encoded.
+    Decode(ImageError),
+    /// Compared images have different dimensions.
+    DimensionMismatch { expected: (u32, u32), found: (u32, u32) },
+    /// A required input (image, path or value) was not provided.
+    Missing(String),
+    /// A recipe file could not be parsed or written.
+    Recipe(String),
+    /// A report could not be generated.
+    Report(String),
+    /// The inspection history database could not be read or written.
+    History(String),
+    /// A frame source could not be opened or grabbed from.
+    Source(String),
+    /// A batch reference mapping could not be parsed.
+    Batch(String),
+    /// The captured image could not be registered onto the reference image.
+    Alignment(String),
+}
+
+/// Result type of fallible Glassvis operations.
+pub type Result<T> = result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "I/O error: {}", err),
+            Error::Decode(err) => write!(f, "Image error: {}", err),
+            Error::DimensionMismatch { expected, found } => write!(
+                f,
+                "Image dimensions differ: expected {}x{}, found {}x{}",
+                expected.0, expected.1, found.0, found.1
+            ),
+            Error::Missing(what) => write!(f, "Missing {}", what),
+            Error::Recipe(msg) => write!(f, "Invalid recipe: {}", msg),
+            Error::Report(msg) => write!(f, "Report error: {}", msg),
+            Error::History(msg) => write!(f, "History error: {}", msg),
+            Error::Source(msg) => write!(f, "Frame source error: {}", msg),
+            Error::Batch(msg) => write!(f, "Batch error: {}", msg),
+            Error::Alignment(msg) => write!(f, "Alignment failed: {}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Decode(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        return Error::Io(err);
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(err: ImageError) -> Error {
+        // keep plain file access failures apart from codec failures
+        match err {
+            ImageError::IoError(err) => Error::Io(err),
+            err => Error::Decode(err),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Error {
+        return Error::History(err.to_string());
+    }
+}
+
+/// Checks that two images have the same dimensions.
+pub fn check_dimensions(expected: (u32, u32), found: (u32, u32)) -> Result<()> {
+    if expected != found {
+        return Err(Error::DimensionMismatch { expected: expected, found: found });
+    }
+
+    return Ok(());
+}
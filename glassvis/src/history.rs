@@ -0,0 +1,318 @@
+//! Inspection history module for Glassvis.
+//!
+//! Every inspection is recorded in an embedded SQLite database ("data/history.db"):
+//! its inputs, settings, metrics, defects and verdict, along with the full report
+//! so that a past inspection can be reopened. Records can be searched by date
+//! range, verdict, product (recipe name) and defect class. Inspection times are
+//! stored as Unix timestamps and only converted to local time for display.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone};
+use rusqlite::{params, Connection};
+
+use crate::aux;
+use crate::classify::DefectClass;
+use crate::error::{Error, Result};
+use crate::report::Report;
+
+/// Database schema, created on first use.
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS inspections (
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        product TEXT NOT NULL,
+        reference TEXT,
+        reference_sha256 TEXT,
+        capture TEXT NOT NULL,
+        capture_sha256 TEXT NOT NULL,
+        image TEXT NOT NULL,
+        settings TEXT NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        defect_count INTEGER NOT NULL,
+        counter INTEGER NOT NULL,
+        defect_rate REAL NOT NULL,
+        score REAL,
+        verdict TEXT NOT NULL,
+        report TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS defects (
+        inspection INTEGER NOT NULL REFERENCES inspections(id) ON DELETE CASCADE,
+        id INTEGER NOT NULL,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        width INTEGER NOT NULL,
+        height INTEGER NOT NULL,
+        area INTEGER NOT NULL,
+        mean_contrast REAL NOT NULL,
+        max_contrast INTEGER NOT NULL,
+        class TEXT NOT NULL,
+        zone TEXT
+    );
+    CREATE INDEX IF NOT EXISTS inspections_timestamp ON inspections(timestamp);
+    CREATE INDEX IF NOT EXISTS defects_class ON defects(class, inspection);
+";
+
+/// Represents the search criteria of recorded inspections (none matches all).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    /// First day of the date range (local time).
+    pub from: Option<NaiveDate>,
+    /// Last day of the date range (local time).
+    pub to: Option<NaiveDate>,
+    /// Verdict name (e.g. "PASS").
+    pub verdict: Option<String>,
+    pub product: Option<String>,
+    /// Class of at least one defect of the inspection.
+    pub class: Option<DefectClass>,
+}
+
+/// Represents a recorded inspection as listed by a search.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub id: i64,
+    /// Inspection time (Unix timestamp in seconds).
+    pub timestamp: i64,
+    pub product: String,
+    pub capture: String,
+    pub defect_count: u32,
+    pub defect_rate: f32,
+    pub verdict: String,
+}
+
+impl Entry {
+    /// Returns the inspection time in local time (YYYY-MM-DD HH:MM:SS).
+    pub fn local_time(&self) -> String {
+        let time = match DateTime::from_timestamp(self.timestamp, 0) {
+            Some(time) => time.with_timezone(&Local),
+            None => return String::new(),
+        };
+
+        return time.format("%Y-%m-%d %H:%M:%S").to_string();
+    }
+}
+
+/// Parses a day of a date range (YYYY-MM-DD).
+pub fn parse_date(text: &str) -> Result<NaiveDate> {
+    let day = NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| Error::History(format!("invalid date '{}', expected YYYY-MM-DD", text)))?;
+
+    return Ok(day);
+}
+
+/// Returns the Unix timestamp of the local midnight starting a day.
+fn day_start(day: NaiveDate) -> i64 {
+    let midnight = day.and_time(NaiveTime::MIN);
+
+    let start = match Local.from_local_datetime(&midnight).earliest() {
+        Some(time) => time.timestamp(),
+        // midnight skipped by a daylight saving change
+        None => midnight.and_utc().timestamp(),
+    };
+
+    return start;
+}
+
+/// Represents an open inspection history database.
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Opens (or creates) a history database file.
+    pub fn open(path: &Path) -> Result<History> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+
+        return Ok(History{ conn: conn });
+    }
+
+    /// Records an inspection report of a product along with its marked image file.
+    /// Returns the id of the new record.
+    pub fn record(&mut self, report: &Report, product: &str, image: &str) -> Result<i64> {
+        let settings = serde_json::to_string(&report.settings).map_err(|err| Error::History(err.to_string()))?;
+        let text = report.to_json()?;
+        let reference = report.reference.as_ref();
+        let timestamp = DateTime::parse_from_rfc3339(&report.timestamp)
+            .map_err(|err| Error::History(format!("invalid timestamp '{}': {}", report.timestamp, err)))?
+            .timestamp();
+
+        let tx = self.conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO inspections (
+                timestamp, product, reference, reference_sha256, capture, capture_sha256, image,
+                settings, width, height, defect_count, counter, defect_rate, score, verdict, report
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            params![
+                timestamp, product,
+                reference.map(|input| input.path.clone()), reference.map(|input| input.sha256.clone()),
+                report.capture.path, report.capture.sha256, image, settings,
+                report.width, report.height, report.defect_count, report.counter,
+                report.defect_rate as f64, report.score.map(|score| score as f64),
+                report.verdict.name(), text,
+            ],
+        )?;
+
+        let id = tx.last_insert_rowid();
+
+        for defect in report.defects.iter() {
+            tx.execute(
+                "INSERT INTO defects (
+                    inspection, id, x, y, width, height, area, mean_contrast, max_contrast, class, zone
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    id, defect.id, defect.x, defect.y, defect.width, defect.height, defect.area,
+                    defect.mean_contrast as f64, defect.max_contrast, defect.class.id(), defect.zone,
+                ],
+            )?;
+        }
+
+        tx.commit()?;
+
+        return Ok(id);
+    }
+
+    /// Lists the recorded inspections matching a filter, newest first.
+    pub fn search(&self, filter: &Filter) -> Result<Vec<Entry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, product, capture, defect_count, defect_rate, verdict
+            FROM inspections
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp < ?2)
+                AND (?3 IS NULL OR verdict = ?3)
+                AND (?4 IS NULL OR product = ?4)
+                AND (?5 IS NULL OR EXISTS (
+                    SELECT 1 FROM defects WHERE defects.inspection = inspections.id AND defects.class = ?5
+                ))
+            ORDER BY timestamp DESC, id DESC",
+        )?;
+
+        // the date range covers whole local days, up to the midnight after the last one
+        let from = filter.from.map(day_start);
+        let to = filter.to.and_then(|day| day.succ_opt()).map(day_start);
+        let class = filter.class.map(|class| class.id());
+        let rows = stmt.query_map(
+            params![from, to, filter.verdict, filter.product, class],
+            |row| {
+                let rate: f64 = row.get(5)?;
+
+                Ok(Entry{
+                    id: row.get(0)?,
+                    timestamp: row.get(1)?,
+                    product: row.get(2)?,
+                    capture: row.get(3)?,
+                    defect_count: row.get(4)?,
+                    defect_rate: rate as f32,
+                    verdict: row.get(6)?,
+                })
+            },
+        )?;
+
+        let mut entries = Vec::new();
+        for entry in rows {
+            entries.push(entry?);
+        }
+
+        return Ok(entries);
+    }
+
+    /// Lists the recorded product names, sorted by name.
+    pub fn products(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT DISTINCT product FROM inspections ORDER BY product")?;
+        let rows = stmt.query_map(params![], |row| row.get(0))?;
+
+        let mut products = Vec::new();
+        for product in rows {
+            products.push(product?);
+        }
+
+        return Ok(products);
+    }
+
+    /// Loads the report of a recorded inspection.
+    pub fn load(&self, id: i64) -> Result<Report> {
+        let text: String = self.conn.query_row(
+            "SELECT report FROM inspections WHERE id = ?1",
+            params![id],
+            |row| row.get(0),
+        )?;
+
+        let report = serde_json::from_str(&text).map_err(|err| Error::History(err.to_string()))?;
+        return Ok(report);
+    }
+}
+
+/// Returns "data/history.db" file path (as path buffer).
+pub fn get_path() -> PathBuf {
+    let mut path = aux::get_path();
+    path.push("history.db");
+    return path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Input;
+    use crate::verdict::Verdict;
+    use crate::Settings;
+
+    /// Returns an empty report of a capture inspected at a time (RFC 3339).
+    fn report(timestamp: &str, capture: &str) -> Report {
+        return Report{
+            timestamp: timestamp.to_string(),
+            reference: None,
+            capture: Input{ path: capture.to_string(), sha256: String::new() },
+            settings: Settings::default(),
+            width: 1,
+            height: 1,
+            defect_count: 0,
+            counter: 0,
+            defect_rate: 0.0,
+            score: None,
+            defects: Vec::new(),
+            zones: Vec::new(),
+            verdict: Verdict::Pass,
+            findings: Vec::new(),
+        };
+    }
+
+    #[test]
+    fn filters_by_local_day() {
+        let path = std::env::temp_dir().join(format!("glassvis-history-{}.db", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut history = History::open(&path).unwrap();
+
+        // the same instant written with different offsets, and one a day later
+        history.record(&report("2024-03-10T12:00:00+00:00", "a.png"), "panel", "a.png").unwrap();
+        history.record(&report("2024-03-10T14:00:00+02:00", "b.png"), "panel", "b.png").unwrap();
+        history.record(&report("2024-03-11T12:00:00Z", "c.png"), "panel", "c.png").unwrap();
+
+        let first = DateTime::from_timestamp(1710072000, 0).unwrap().with_timezone(&Local).date_naive();
+        let filter = Filter{ from: Some(first), to: Some(first), ..Filter::default() };
+        let entries = history.search(&filter).unwrap();
+
+        let captures: Vec<&str> = entries.iter().map(|entry| entry.capture.as_str()).collect();
+        assert_eq!(captures, vec!["b.png", "a.png"]);
+        assert!(entries.iter().all(|entry| entry.timestamp == 1710072000));
+
+        let all = history.search(&Filter::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].capture, "c.png");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse_date("2024-03-10").unwrap(), NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+        assert!(matches!(parse_date("10.03.2024"), Err(Error::History(_))));
+    }
+}
@@ -0,0 +1,312 @@
+//! HTML report module for Glassvis.
+//!
+//! Renders an inspection report as a single self-contained HTML page that opens
+//! in any browser: images are embedded as base64 PNGs, the reference and captured
+//! images are shown side by side with a shared zoom, each defect has a crop that
+//! zooms on click, and the defect table sorts by any column.
+
+use image::{DynamicImage, ImageOutputFormat};
+
+use crate::classify::DefectClass;
+use crate::error::{Error, Result};
+use crate::report::{self, Images, Report};
+use crate::verdict::Verdict;
+
+/// Page style.
+const STYLE: &str = r#"
+body { font-family: sans-serif; margin: 24px; color: #1a1a1a; }
+h1 { display: flex; align-items: center; gap: 16px; }
+h2 { margin-top: 28px; border-bottom: 1px solid #ccc; }
+.verdict { color: #fff; padding: 4px 16px; font-size: 20px; }
+.pass { background: #339a4d; }
+.fail { background: #cc3333; }
+.review { background: #e68a00; }
+table { border-collapse: collapse; }
+td, th { padding: 4px 10px; text-align: left; border-bottom: 1px solid #eee; }
+th.sort { cursor: pointer; user-select: none; }
+th.sort:after { content: " \2195"; color: #999; }
+code { font-size: 12px; }
+pre { background: #f4f4f4; padding: 8px; }
+.viewer { display: flex; gap: 12px; }
+.pane { flex: 1; overflow: auto; height: 70vh; border: 1px solid #ccc; }
+.pane img, .crop, #zoom img { image-rendering: pixelated; }
+.crop { height: 48px; cursor: zoom-in; border: 1px solid #ccc; }
+#zoom { display: none; position: fixed; inset: 0; background: rgba(0, 0, 0, 0.8); align-items: center; justify-content: center; cursor: zoom-out; }
+#zoom img { width: 60vmin; }
+"#;
+
+/// Page behavior: synchronized zoom and scrolling, table sorting and crop zooming.
+const SCRIPT: &str = r#"
+const panes = document.querySelectorAll('.pane');
+document.getElementById('scale').addEventListener('input', (e) => {
+  document.querySelectorAll('.pane img').forEach((img) => img.style.width = (img.naturalWidth * e.target.value) + 'px');
+});
+panes.forEach((pane) => pane.addEventListener('scroll', () => {
+  panes.forEach((other) => { other.scrollLeft = pane.scrollLeft; other.scrollTop = pane.scrollTop; });
+}));
+document.getElementById('marked').addEventListener('change', (e) => {
+  const img = document.getElementById('capture');
+  img.dataset.marked = img.dataset.marked || img.src;
+  img.src = e.target.checked ? img.dataset.marked : img.dataset.plain;
+});
+document.querySelectorAll('th.sort').forEach((th, col) => th.addEventListener('click', () => {
+  const body = th.closest('table').tBodies[0];
+  const rows = Array.from(body.rows);
+  const asc = th.dataset.asc !== 'true';
+  const key = (row) => row.cells[col + 1].textContent;
+  rows.sort((a, b) => {
+    const x = key(a), y = key(b);
+    const cmp = isNaN(x) || isNaN(y) ? x.localeCompare(y) : x - y;
+    return asc ? cmp : -cmp;
+  });
+  th.dataset.asc = asc;
+  rows.forEach((row) => body.appendChild(row));
+}));
+const zoom = document.getElementById('zoom');
+document.querySelectorAll('.crop').forEach((crop) => crop.addEventListener('click', () => {
+  zoom.firstElementChild.src = crop.src;
+  zoom.style.display = 'flex';
+}));
+zoom.addEventListener('click', () => zoom.style.display = 'none');
+"#;
+
+/// Escapes text for use in HTML content and attributes.
+fn escape(text: &str) -> String {
+    let mut dst = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&'  => dst.push_str("&amp;"),
+            '<'  => dst.push_str("&lt;"),
+            '>'  => dst.push_str("&gt;"),
+            '"'  => dst.push_str("&quot;"),
+            '\'' => dst.push_str("&#39;"),
+            c    => dst.push(c),
+        }
+    }
+
+    return dst;
+}
+
+/// Encodes an image as a base64 PNG data URI.
+fn to_data_uri(src: &DynamicImage) -> Result<String> {
+    // marked pixels carry zero alpha, so drop the alpha channel
+    let rgb = DynamicImage::ImageRgb8(src.to_rgb8());
+    let mut png = Vec::new();
+    rgb.write_to(&mut png, ImageOutputFormat::Png)?;
+
+    return Ok(format!("data:image/png;base64,{}", base64::encode(&png)));
+}
+
+/// Writes the inputs and summary tables.
+fn write_summary(html: &mut String, report: &Report) {
+    html.push_str("<h2>Inputs</h2>\n<table>\n");
+
+    if let Some(reference) = &report.reference {
+        html.push_str(&format!("<tr><th>Reference</th><td>{}</td><td><code>{}</code></td></tr>\n",
+            escape(&reference.path), reference.sha256));
+    }
+
+    html.push_str(&format!("<tr><th>Capture</th><td>{}</td><td><code>{}</code></td></tr>\n</table>\n",
+        escape(&report.capture.path), report.capture.sha256));
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    html.push_str(&format!("<tr><th>Image size</th><td>{} x {} px</td></tr>\n", report.width, report.height));
+    html.push_str(&format!("<tr><th>Defects</th><td>{}</td></tr>\n", report.defect_count));
+    html.push_str(&format!("<tr><th>Defect rate</th><td>{:.4}% ({} px)</td></tr>\n", report.defect_rate, report.counter));
+
+    if let Some(score) = report.score {
+        html.push_str(&format!("<tr><th>SSIM</th><td>{:.4}</td></tr>\n", score));
+    }
+
+    for class in DefectClass::ALL.iter() {
+        let count = report.defects.iter().filter(|defect| defect.class == *class).count();
+        if count > 0 {
+            html.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", class.name(), count));
+        }
+    }
+
+    html.push_str("</table>\n");
+
+    if report.zones.is_empty() == false {
+        html.push_str("<h2>Zones</h2>\n<table>\n");
+        html.push_str("<tr><th>Zone</th><th>Defects</th><th>Allowed</th><th>Defect px</th><th>Area px</th><th></th></tr>\n");
+
+        for zone in report.zones.iter() {
+            let mark = if zone.passed { "pass" } else { "fail" };
+            html.push_str(&format!("<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&zone.name), zone.defects, zone.max_defects, zone.counter, zone.area, mark));
+        }
+
+        html.push_str("</table>\n");
+    }
+}
+
+/// Writes the side-by-side image viewer.
+fn write_viewer(html: &mut String, images: &Images) -> Result<()> {
+    let capture = to_data_uri(images.capture)?;
+    let marked = to_data_uri(images.annotated)?;
+
+    html.push_str("<h2>Images</h2>\n");
+    html.push_str("<p>Zoom <input type=\"range\" id=\"scale\" min=\"0.25\" max=\"8\" step=\"0.25\" value=\"1\"> \
+        <label><input type=\"checkbox\" id=\"marked\" checked> Show defects</label></p>\n");
+    html.push_str("<div class=\"viewer\">\n");
+
+    if let Some(reference) = images.reference {
+        html.push_str(&format!("<div class=\"pane\"><img src=\"{}\" alt=\"Reference\"></div>\n", to_data_uri(reference)?));
+    }
+
+    html.push_str(&format!("<div class=\"pane\"><img id=\"capture\" src=\"{}\" data-plain=\"{}\" alt=\"Capture\"></div>\n",
+        marked, capture));
+    html.push_str("</div>\n");
+
+    return Ok(());
+}
+
+/// Writes the sortable defect table with a crop of each defect.
+fn write_defects(html: &mut String, report: &Report, images: &Images) -> Result<()> {
+    let columns = [
+        "ID", "Class", "X", "Y", "Width", "Height", "Area", "Mean", "Max",
+        "Elongation", "Circularity", "Solidity", "Angle", "Zone",
+    ];
+
+    html.push_str("<h2>Defects</h2>\n");
+
+    if report.defects.is_empty() {
+        html.push_str("<p>No defects found.</p>\n");
+        return Ok(());
+    }
+
+    html.push_str("<table>\n<thead><tr><th>Crop</th>");
+    for column in columns.iter() {
+        html.push_str(&format!("<th class=\"sort\">{}</th>", column));
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+
+    for defect in report.defects.iter() {
+        let crop = to_data_uri(&report::crop(images.annotated, defect))?;
+        let f = &defect.features;
+
+        html.push_str(&format!("<tr><td><img class=\"crop\" src=\"{}\" alt=\"Defect {}\"></td>\
+            <td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.2}</td><td>{}</td>\
+            <td>{:.2}</td><td>{:.2}</td><td>{:.2}</td><td>{:.1}</td><td>{}</td></tr>\n",
+            crop, defect.id,
+            defect.id, defect.class.name(), defect.x, defect.y, defect.width, defect.height, defect.area,
+            defect.mean_contrast, defect.max_contrast,
+            f.elongation, f.circularity, f.solidity, f.orientation,
+            escape(defect.zone.as_deref().unwrap_or("-"))));
+    }
+
+    html.push_str("</tbody>\n</table>\n");
+
+    return Ok(());
+}
+
+/// Renders an inspection report into a self-contained HTML page.
+pub fn render(report: &Report, images: &Images) -> Result<String> {
+    let mut html = String::new();
+    let verdict_class = match report.verdict {
+        Verdict::Pass   => "pass",
+        Verdict::Review => "review",
+        Verdict::Fail   => "fail",
+    };
+
+    let settings = serde_json::to_string_pretty(&report.settings).map_err(|err| Error::Report(err.to_string()))?;
+
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>Glassvis Inspection Report - {}</title>\n", escape(&report.capture.path)));
+    html.push_str(&format!("<style>{}</style>\n</head>\n<body>\n", STYLE));
+    html.push_str(&format!("<h1>Glassvis Inspection Report <span class=\"verdict {}\">{}</span></h1>\n", verdict_class, report.verdict.name()));
+    html.push_str(&format!("<p>Inspected: {}</p>\n", escape(&report.timestamp)));
+
+    if report.findings.is_empty() == false {
+        html.push_str("<ul>\n");
+        for finding in report.findings.iter() {
+            html.push_str(&format!("<li>{}: {}</li>\n", finding.verdict.name(), escape(&finding.rule)));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    write_summary(&mut html, report);
+    write_viewer(&mut html, images)?;
+    write_defects(&mut html, report, images)?;
+
+    html.push_str(&format!("<h2>Settings</h2>\n<pre>{}</pre>\n", escape(&settings)));
+    html.push_str("<div id=\"zoom\"><img alt=\"Zoomed defect\"></div>\n");
+    html.push_str(&format!("<script>{}</script>\n</body>\n</html>\n", SCRIPT));
+
+    return Ok(html);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classify::Features;
+    use crate::report::{DefectRecord, Input};
+    use crate::Settings;
+
+    /// Creates a defect record of given id, position and zone.
+    fn defect(id: u32, x: i32, y: i32, zone: &str) -> DefectRecord {
+        return DefectRecord{
+            id: id,
+            x: x,
+            y: y,
+            width: 3,
+            height: 2,
+            area: 5,
+            centroid: [x as f32 + 1.0, y as f32 + 0.5],
+            mean_contrast: 50.0,
+            max_contrast: 80,
+            class: DefectClass::Spot,
+            features: Features::default(),
+            zone: Some(zone.to_string()),
+        };
+    }
+
+    /// Creates a failed report of a 40x30 capture with two defects.
+    fn report() -> Report {
+        return Report{
+            timestamp: String::from("2024-05-01T10:00:00+02:00"),
+            reference: Some(Input{ path: String::from("ref.png"), sha256: String::from("ab12") }),
+            capture: Input{ path: String::from("panel <7>.png"), sha256: String::from("cd34") },
+            settings: Settings::default(),
+            width: 40,
+            height: 30,
+            defect_count: 2,
+            counter: 10,
+            defect_rate: 0.83,
+            score: None,
+            defects: vec![defect(1, 5, 5, "vision"), defect(2, 30, 20, "band \"B\"")],
+            zones: Vec::new(),
+            verdict: Verdict::Fail,
+            findings: Vec::new(),
+        };
+    }
+
+    #[test]
+    fn escapes_special_characters() {
+        assert_eq!(escape("a & b"), "a &amp; b");
+        assert_eq!(escape("<b>\"it's\"</b>"), "&lt;b&gt;&quot;it&#39;s&quot;&lt;/b&gt;");
+        assert_eq!(escape("plain"), "plain");
+    }
+
+    #[test]
+    fn renders_self_contained_page() {
+        let image = DynamicImage::new_rgb8(40, 30);
+        let images = Images{ reference: Some(&image), capture: &image, annotated: &image };
+        let html = render(&report(), &images).unwrap();
+
+        // reference, marked and plain capture, and one crop per defect are embedded
+        let sources: Vec<&str> = html.split("src=\"").skip(1).collect();
+        assert_eq!(sources.len(), 4);
+        assert!(sources.iter().all(|src| src.starts_with("data:image/png;base64,")));
+        assert_eq!(html.matches("data-plain=\"data:image/png;base64,").count(), 1);
+        assert!(html.contains("<link") == false);
+
+        assert!(html.contains("alt=\"Defect 1\""));
+        assert!(html.contains("alt=\"Defect 2\""));
+        assert!(html.contains("band &quot;B&quot;"));
+        assert!(html.contains("panel &lt;7&gt;.png"));
+        assert!(html.contains("<span class=\"verdict fail\">FAIL</span>"));
+    }
+}
@@ -0,0 +1,1131 @@
+//! Image processing module for Glassvis application.
+
+use std::f32;
+use std::fs;
+use std::path::Path;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImage, GenericImageView, GrayImage, ImageBuffer, RgbImage, RgbaImage, Luma, Pixel, Rgb, Rgba};
+use imageproc::contrast::{adaptive_threshold, equalize_histogram, otsu_level, threshold};
+use imageproc::corners::{Corner, corners_fast9, corners_fast12};
+use imageproc::drawing::draw_hollow_rect_mut;
+use imageproc::definitions::{Clamp, HasWhite};
+use imageproc::edges::canny;
+use imageproc::distance_transform::Norm;
+use imageproc::filter::box_filter;
+use imageproc::gradients::{horizontal_sobel, vertical_sobel, sobel_gradients, horizontal_prewitt, vertical_prewitt, prewitt_gradients};
+use imageproc::morphology::{close, dilate, erode, open as open_morph};
+use imageproc::map::{red_channel, green_channel, blue_channel, map_pixels, map_subpixels};
+use imageproc::rect::Rect;
+use imageproc::region_labelling::{connected_components, Connectivity};
+use imageproc::utils::{pixel_diffs};
+
+use serde::{Deserialize, Serialize};
+
+use crate::classify::{self, DefectClass, Features};
+use crate::error::{check_dimensions, Result};
+
+/// Represents a 2D point.
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Represents a single connected defect region found in a diff mask.
+pub struct Defect {
+    pub id: u32,
+    pub rect: Rect,
+    pub area: u32,
+    pub centroid: (f32, f32),
+    pub mean_contrast: f32,
+    pub max_contrast: u8,
+    /// Name of the zone the defect lies in (zone grading only).
+    pub zone: Option<String>,
+    pub features: Features,
+    pub class: DefectClass,
+}
+
+/// Represents the available image comparison modes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DiffMode {
+    #[serde(rename = "luma")]
+    Luma,
+    #[serde(rename = "ssim")]
+    Ssim,
+    #[serde(rename = "de76")]
+    DeltaE76,
+    #[serde(rename = "de94")]
+    DeltaE94,
+    #[serde(rename = "de2000")]
+    DeltaE2000,
+}
+
+impl DiffMode {
+    /// Parses a diff mode from its settings panel identifier.
+    pub fn from_id(id: &str) -> DiffMode {
+        let mode = match id {
+            "luma"   => DiffMode::Luma,
+            "ssim"   => DiffMode::Ssim,
+            "de76"   => DiffMode::DeltaE76,
+            "de94"   => DiffMode::DeltaE94,
+            "de2000" => DiffMode::DeltaE2000,
+            _        => DiffMode::Luma,
+        };
+        
+        return mode;
+    }
+
+    /// Returns the settings panel identifier of a diff mode.
+    pub fn id(&self) -> &'static str {
+        let id = match self {
+            DiffMode::Luma       => "luma",
+            DiffMode::Ssim       => "ssim",
+            DiffMode::DeltaE76   => "de76",
+            DiffMode::DeltaE94   => "de94",
+            DiffMode::DeltaE2000 => "de2000",
+        };
+        
+        return id;
+    }
+}
+
+/// Represents morphological operations applicable to a diff mask.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MorphOp {
+    None,
+    Erode,
+    Dilate,
+    Open,
+    Close,
+}
+
+impl MorphOp {
+    /// Parses a morphological operation from its settings panel identifier.
+    pub fn from_id(id: &str) -> MorphOp {
+        let op = match id {
+            "erode"  => MorphOp::Erode,
+            "dilate" => MorphOp::Dilate,
+            "open"   => MorphOp::Open,
+            "close"  => MorphOp::Close,
+            _        => MorphOp::None,
+        };
+        
+        return op;
+    }
+
+    /// Returns the settings panel identifier of a morphological operation.
+    pub fn id(&self) -> &'static str {
+        let id = match self {
+            MorphOp::None   => "none",
+            MorphOp::Erode  => "erode",
+            MorphOp::Dilate => "dilate",
+            MorphOp::Open   => "open",
+            MorphOp::Close  => "close",
+        };
+        
+        return id;
+    }
+}
+
+/// Represents structuring element shapes used by morphological operations.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kernel {
+    Square,
+    Diamond,
+}
+
+impl Kernel {
+    /// Parses a structuring element from its settings panel identifier.
+    pub fn from_id(id: &str) -> Kernel {
+        let kernel = match id {
+            "diamond" => Kernel::Diamond,
+            _         => Kernel::Square,
+        };
+        
+        return kernel;
+    }
+    
+    /// Returns the settings panel identifier of a structuring element.
+    pub fn id(&self) -> &'static str {
+        let id = match self {
+            Kernel::Square  => "square",
+            Kernel::Diamond => "diamond",
+        };
+        
+        return id;
+    }
+    
+    /// Returns the distance norm whose unit ball matches the structuring element.
+    fn norm(&self) -> Norm {
+        let norm = match self {
+            Kernel::Square  => Norm::LInf,
+            Kernel::Diamond => Norm::L1,
+        };
+        
+        return norm;
+    }
+}
+
+/// Represents post-processing settings applied to a diff mask.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Filter {
+    pub op: MorphOp,
+    pub kernel: Kernel,
+    pub radius: u8,
+    pub min_area: u32,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        return Filter{
+            op: MorphOp::None,
+            kernel: Kernel::Square,
+            radius: 1,
+            min_area: 1,
+        };
+    }
+}
+
+/// Creates a dynamic image buffer from a specified image file.
+pub fn open(img_file: &str) -> Result<DynamicImage> {
+    let src = image::open(img_file)?;
+    
+    return Ok(src);
+}
+
+/// Creates a grayscale image buffer from a specified image file.
+pub fn open_luma(img_file: &str) -> Result<GrayImage> {
+    let src = image::open(img_file)?.to_luma8();
+
+    return Ok(src);
+}
+
+/// Creates a grayscale image by extracting the specified channel of an RGB image.
+pub fn extract_channel(src: RgbImage, channel: &str) -> GrayImage {
+    let dst = match channel {
+        "green" => green_channel(&src),
+        "red"   => red_channel(&src),
+        "blue"  => blue_channel(&src),
+        _        => green_channel(&src),
+    };
+    
+    return dst;
+}
+
+/// Resizes an image to a given width and height keeping the aspect ratio.
+pub fn resize(src: DynamicImage, w: u32, h: u32) -> DynamicImage {
+    return src.resize(w, h, FilterType::CatmullRom).unsharpen(0.25, 0);
+}
+
+/// Creates the parent directory of an output file if necessary.
+fn create_parent(file_path: &Path) -> Result<()> {
+    if let Some(dir) = file_path.parent() {
+        if !dir.as_os_str().is_empty() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    
+    return Ok(());
+}
+
+/// Saves an image to an image file.
+pub fn save(src: &DynamicImage, img_file: &str) -> Result<()> {
+    let file_path = Path::new(img_file);
+    create_parent(file_path)?;
+    src.save(file_path)?;
+    
+    return Ok(());
+}
+
+// Saves a grayscale image to an image file.
+pub fn save_luma(src: &GrayImage, img_file: &str) -> Result<()> {
+    let file_path = Path::new(img_file);
+    create_parent(file_path)?;
+    src.save(file_path)?;
+    
+    return Ok(());
+}
+
+/// Applies Canny edge detection filter to input image.
+pub fn apply_canny(src: &GrayImage, low: f32, high: f32) -> GrayImage {
+    let dst = canny(src, low, high);
+    return dst;
+}
+
+/// Applies sobel filter to input image.
+pub fn sobel(src: &GrayImage) -> GrayImage {
+    let filtered = sobel_gradients(src);
+    let dst = map_subpixels(&filtered, <u8 as Clamp<u16>>::clamp);
+    return dst;
+}
+
+/// Applies horizontal sobel filter to input image.
+pub fn sobel_horizon(src: &GrayImage) -> GrayImage {
+    let filtered = horizontal_sobel(src);
+    let dst = map_subpixels(&filtered, |x| x as u8);
+    return dst;
+}
+
+/// Applies vertical sobel filter to input image.
+pub fn sobel_vertic(src: &GrayImage) -> GrayImage {
+    let filtered = vertical_sobel(src);
+    let dst = map_subpixels(&filtered, |x| x as u8);
+    return dst;
+}
+
+/// Applies prewitt filter to input image.
+pub fn prewitt(src: &GrayImage) -> GrayImage {
+    let filtered = prewitt_gradients(src);
+    let dst = map_subpixels(&filtered, <u8 as Clamp<u16>>::clamp);
+    return dst;
+}
+
+/// Applies horizontal prewitt filter to input image.
+pub fn prewitt_horizon(src: &GrayImage) -> GrayImage {
+    let filtered = horizontal_prewitt(src);
+    let dst = map_subpixels(&filtered, |x| x as u8);
+    return dst;
+}
+
+/// Applies vertical prewitt filter to input image.
+pub fn prewitt_vertic(src: &GrayImage) -> GrayImage {
+    let filtered = vertical_prewitt(src);
+    let dst = map_subpixels(&filtered, |x| x as u8);
+    return dst;
+}
+
+/// Detects corners, also known as interest points, using FAST-9 features.
+pub fn detect_corners_f9(src: &GrayImage, level: u8) -> Vec<Corner> {
+    let corners = corners_fast9(src, level);
+    return corners;
+}
+
+/// Detects corners, also known as interest points, using FAST-12 features.
+pub fn detect_corners_f12(src: &GrayImage, level: u8) -> Vec<Corner> {
+    let corners = corners_fast12(src, level);
+    return corners;
+}
+
+/// Marks corners using the specified color.
+pub fn mark_corners(src: &GrayImage, corners: Vec<Corner>) -> GrayImage {
+    let (width, height) = src.dimensions();
+    let mut dst = ImageBuffer::new(width, height);
+    let white = Luma::white();
+    
+    for corner in corners.iter() {
+        dst.put_pixel(corner.x, corner.y, white);
+    }
+    return dst;
+}
+
+/// Applies histogram equalization filter to input image.
+pub fn equalize_hist(src: &GrayImage) -> GrayImage {
+    let dst = equalize_histogram(src);
+    return dst;
+}
+
+/// Applies an adaptive threshold to supplied image.
+pub fn adaptive_thresh(src: &GrayImage, rad: u32) -> GrayImage {
+    let dst = adaptive_threshold(src, rad);
+    return dst;
+}
+
+/// Applies an otsu threshold to supplied image.
+pub fn otsu_thresh(src: &GrayImage) -> GrayImage {
+    let level = otsu_level(src);
+    let dst = threshold(src, level);
+    return dst;
+}
+
+/// Finds isolated bright or dark spots in a single image and returns them as a contrast mask.
+/// Spots are pixels deviating from their local mean by more than both the otsu level of all
+/// deviations and the defect significance level, grouped into blobs no wider than the window.
+pub fn spot_mask(src: &GrayImage, rad: u32, significance: u8) -> GrayImage {
+    let (width, height) = src.dimensions();
+    let mean = box_filter(src, rad, rad);
+    
+    // absolute deviation from the local background
+    let mut dev = GrayImage::new(width, height);
+    for (x, y, pix) in src.enumerate_pixels() {
+        let p = pix[0];
+        let q = mean.get_pixel(x, y)[0];
+        dev.put_pixel(x, y, Luma([p.abs_diff(q)]));
+    }
+    
+    let level = 255 / significance;
+    let binary = otsu_thresh(&dev);
+    
+    let mut mask = GrayImage::new(width, height);
+    for (x, y, pix) in dev.enumerate_pixels() {
+        if binary.get_pixel(x, y)[0] > 0 && pix[0] > level {
+            mask.put_pixel(x, y, *pix);
+        }
+    }
+    
+    // drop blobs too large to be isolated spots
+    let labels = connected_components(&threshold(&mask, 0), Connectivity::Eight, Luma([0u8]));
+    let mut bounds: Vec<(u32, u32, u32, u32)> = Vec::new();
+    
+    for (x, y, label) in labels.enumerate_pixels() {
+        let id = label[0] as usize;
+        while bounds.len() <= id {
+            bounds.push((u32::MAX, u32::MAX, 0, 0));
+        }
+        let bound = &mut bounds[id];
+        *bound = (bound.0.min(x), bound.1.min(y), bound.2.max(x), bound.3.max(y));
+    }
+    
+    let limit = 2 * rad + 1;
+    for (x, y, label) in labels.enumerate_pixels() {
+        let (left, top, right, bottom) = bounds[label[0] as usize];
+        if label[0] > 0 && (right - left + 1 > limit || bottom - top + 1 > limit) {
+            mask.put_pixel(x, y, Luma([0u8]));
+        }
+    }
+    
+    return mask;
+}
+
+/// Counts bright and dark spots according to the adaptive threshold at their centroids.
+pub fn spot_polarity(src: &GrayImage, defects: &Vec<Defect>, rad: u32) -> (u32, u32) {
+    let bright_map = adaptive_thresh(src, rad);
+    let mut bright = 0;
+    let mut dark = 0;
+    
+    for defect in defects.iter() {
+        let x = defect.centroid.0.round() as u32;
+        let y = defect.centroid.1.round() as u32;
+        
+        if bright_map.get_pixel(x, y)[0] > 0 {
+            bright += 1;
+        } else {
+            dark += 1;
+        }
+    }
+    
+    return (bright, dark);
+}
+
+/// Finds pixel diffs between specified images and returns them as a contrast mask.
+pub fn diff_mask(src: &DynamicImage, dst: &DynamicImage, significance: u8) -> Result<GrayImage> {
+    check_dimensions(src.dimensions(), dst.dimensions())?;
+    
+    let diffs = pixel_diffs(src, dst, |p, q| p != q);
+    let level = 255 / significance;
+    
+    let (width, height) = dst.dimensions();
+    let mut mask = GrayImage::new(width, height);
+
+    // filter diffs according to the defect significance
+    for diff in diffs.iter() {
+        let src_pix = src.get_pixel(diff.x, diff.y).to_luma()[0];
+        let dst_pix = dst.get_pixel(diff.x, diff.y).to_luma()[0];
+
+        if src_pix > dst_pix && src_pix - dst_pix > level {
+            mask.put_pixel(diff.x, diff.y, Luma([src_pix - dst_pix]));
+        } else if src_pix < dst_pix && dst_pix - src_pix > level {
+            mask.put_pixel(diff.x, diff.y, Luma([dst_pix - src_pix]));
+        }
+    }
+
+    return Ok(mask);
+}
+
+/// Computes windowed structural similarity (SSIM) between two grayscale images.
+/// Returns the global SSIM index and a per-pixel dissimilarity map.
+pub fn ssim(src: &GrayImage, dst: &GrayImage, rad: u32) -> Result<(f32, GrayImage)> {
+    check_dimensions(src.dimensions(), dst.dimensions())?;
+    
+    let (width, height) = src.dimensions();
+    let mut map = GrayImage::new(width, height);
+    
+    // stabilizing constants for 8-bit dynamic range
+    let c1 = (0.01 * 255.0) * (0.01 * 255.0);
+    let c2 = (0.03 * 255.0) * (0.03 * 255.0);
+    
+    // summed area tables of x, y, x², y² and xy
+    let stride = (width + 1) as usize;
+    let mut sums = vec![[0f64; 5]; stride * (height + 1) as usize];
+    
+    for y in 0..height {
+        let mut row = [0f64; 5];
+        for x in 0..width {
+            let p = src.get_pixel(x, y)[0] as f64;
+            let q = dst.get_pixel(x, y)[0] as f64;
+            let vals = [p, q, p * p, q * q, p * q];
+            
+            let above = sums[y as usize * stride + x as usize + 1];
+            let cell = &mut sums[(y + 1) as usize * stride + x as usize + 1];
+            for k in 0..5 {
+                row[k] += vals[k];
+                cell[k] = above[k] + row[k];
+            }
+        }
+    }
+    
+    let mut total = 0f64;
+    
+    for y in 0..height {
+        for x in 0..width {
+            // clip the window to image borders
+            let left   = x.saturating_sub(rad) as usize;
+            let top    = y.saturating_sub(rad) as usize;
+            let right  = (x + rad + 1).min(width) as usize;
+            let bottom = (y + rad + 1).min(height) as usize;
+            let n = ((right - left) * (bottom - top)) as f64;
+            
+            let mut s = [0f64; 5];
+            for k in 0..5 {
+                s[k] = sums[bottom * stride + right][k] - sums[top * stride + right][k]
+                     - sums[bottom * stride + left][k] + sums[top * stride + left][k];
+            }
+            
+            let mu_p = s[0] / n;
+            let mu_q = s[1] / n;
+            let var_p = s[2] / n - mu_p * mu_p;
+            let var_q = s[3] / n - mu_q * mu_q;
+            let cov = s[4] / n - mu_p * mu_q;
+            
+            let index = ((2.0 * mu_p * mu_q + c1) * (2.0 * cov + c2))
+                / ((mu_p * mu_p + mu_q * mu_q + c1) * (var_p + var_q + c2));
+            total += index;
+            
+            // map SSIM range [-1, 1] onto dissimilarity range [0, 255]
+            let dissim = ((1.0 - index) / 2.0 * 255.0).round().clamp(0.0, 255.0);
+            map.put_pixel(x, y, Luma([dissim as u8]));
+        }
+    }
+    
+    let index = (total / (width as f64 * height as f64)) as f32;
+    
+    return Ok((index, map));
+}
+
+/// Keeps the values of a dissimilarity map that exceed the defect significance level.
+pub fn threshold_mask(map: &GrayImage, significance: u8) -> GrayImage {
+    let level = 255 / significance;
+    let mask = map_pixels(map, |_, _, p: Luma<u8>| {
+        if p[0] > level {
+            return p;
+        }
+        return Luma([0u8]);
+    });
+    
+    return mask;
+}
+
+/// Converts an sRGB pixel to CIELAB color space (D65 white point).
+pub fn to_lab(pix: Rgb<u8>) -> [f32; 3] {
+    // undo sRGB gamma companding
+    let linear = |c: u8| {
+        let v = c as f32 / 255.0;
+        if v <= 0.04045 {
+            return v / 12.92;
+        }
+        return ((v + 0.055) / 1.055).powf(2.4);
+    };
+    
+    let r = linear(pix[0]);
+    let g = linear(pix[1]);
+    let b = linear(pix[2]);
+    
+    // linear RGB to XYZ normalized by the D65 reference white
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y =  0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    
+    let f = |t: f32| {
+        if t > 0.008856 {
+            return t.cbrt();
+        }
+        return 7.787 * t + 16.0 / 116.0;
+    };
+    
+    let fx = f(x);
+    let fy = f(y);
+    let fz = f(z);
+    
+    return [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)];
+}
+
+/// Computes CIE76 color difference between two CIELAB colors.
+pub fn delta_e76(p: &[f32; 3], q: &[f32; 3]) -> f32 {
+    let dl = p[0] - q[0];
+    let da = p[1] - q[1];
+    let db = p[2] - q[2];
+    
+    return (dl * dl + da * da + db * db).sqrt();
+}
+
+/// Computes CIE94 color difference (graphic arts weights) between two CIELAB colors.
+pub fn delta_e94(p: &[f32; 3], q: &[f32; 3]) -> f32 {
+    let c1 = (p[1] * p[1] + p[2] * p[2]).sqrt();
+    let c2 = (q[1] * q[1] + q[2] * q[2]).sqrt();
+    
+    let dl = p[0] - q[0];
+    let dc = c1 - c2;
+    let da = p[1] - q[1];
+    let db = p[2] - q[2];
+    let dh2 = (da * da + db * db - dc * dc).max(0.0);
+    
+    let sc = 1.0 + 0.045 * c1;
+    let sh = 1.0 + 0.015 * c1;
+    
+    return (dl * dl + (dc / sc) * (dc / sc) + dh2 / (sh * sh)).sqrt();
+}
+
+/// Computes CIEDE2000 color difference between two CIELAB colors.
+pub fn delta_e2000(p: &[f32; 3], q: &[f32; 3]) -> f32 {
+    let (l1, a1, b1) = (p[0] as f64, p[1] as f64, p[2] as f64);
+    let (l2, a2, b2) = (q[0] as f64, q[1] as f64, q[2] as f64);
+    
+    let c1 = (a1 * a1 + b1 * b1).sqrt();
+    let c2 = (a2 * a2 + b2 * b2).sqrt();
+    let c_avg = (c1 + c2) / 2.0;
+    let c_avg7 = c_avg.powi(7);
+    let g = 0.5 * (1.0 - (c_avg7 / (c_avg7 + 25f64.powi(7))).sqrt());
+    
+    let a1p = (1.0 + g) * a1;
+    let a2p = (1.0 + g) * a2;
+    let c1p = (a1p * a1p + b1 * b1).sqrt();
+    let c2p = (a2p * a2p + b2 * b2).sqrt();
+    
+    // hue angles in degrees within [0, 360)
+    let hue = |b: f64, a: f64| {
+        if a == 0.0 && b == 0.0 {
+            return 0.0;
+        }
+        let h = b.atan2(a).to_degrees();
+        if h < 0.0 {
+            return h + 360.0;
+        }
+        return h;
+    };
+    
+    let h1p = hue(b1, a1p);
+    let h2p = hue(b2, a2p);
+    
+    let dlp = l2 - l1;
+    let dcp = c2p - c1p;
+    
+    let mut dhp = 0.0;
+    if c1p * c2p != 0.0 {
+        dhp = h2p - h1p;
+        if dhp > 180.0 {
+            dhp -= 360.0;
+        } else if dhp < -180.0 {
+            dhp += 360.0;
+        }
+    }
+    let dhp_big = 2.0 * (c1p * c2p).sqrt() * (dhp / 2.0).to_radians().sin();
+    
+    let l_avg = (l1 + l2) / 2.0;
+    let cp_avg = (c1p + c2p) / 2.0;
+    
+    let mut hp_avg = h1p + h2p;
+    if c1p * c2p != 0.0 {
+        if (h1p - h2p).abs() <= 180.0 {
+            hp_avg /= 2.0;
+        } else if h1p + h2p < 360.0 {
+            hp_avg = (hp_avg + 360.0) / 2.0;
+        } else {
+            hp_avg = (hp_avg - 360.0) / 2.0;
+        }
+    }
+    
+    let t = 1.0 - 0.17 * (hp_avg - 30.0).to_radians().cos()
+        + 0.24 * (2.0 * hp_avg).to_radians().cos()
+        + 0.32 * (3.0 * hp_avg + 6.0).to_radians().cos()
+        - 0.20 * (4.0 * hp_avg - 63.0).to_radians().cos();
+    
+    let d_theta = 30.0 * (-((hp_avg - 275.0) / 25.0).powi(2)).exp();
+    let cp_avg7 = cp_avg.powi(7);
+    let rc = 2.0 * (cp_avg7 / (cp_avg7 + 25f64.powi(7))).sqrt();
+    let l50 = (l_avg - 50.0) * (l_avg - 50.0);
+    let sl = 1.0 + 0.015 * l50 / (20.0 + l50).sqrt();
+    let sc = 1.0 + 0.045 * cp_avg;
+    let sh = 1.0 + 0.015 * cp_avg * t;
+    let rt = -(2.0 * d_theta).to_radians().sin() * rc;
+    
+    let dl = dlp / sl;
+    let dc = dcp / sc;
+    let dh = dhp_big / sh;
+    
+    return (dl * dl + dc * dc + dh * dh + rt * dc * dh).sqrt() as f32;
+}
+
+/// Finds pixels whose perceptual color difference exceeds the threshold.
+/// Mask values hold the rounded color difference of each flagged pixel.
+pub fn color_mask(src: &DynamicImage, dst: &DynamicImage, mode: DiffMode, thresh: f32) -> Result<GrayImage> {
+    check_dimensions(src.dimensions(), dst.dimensions())?;
+    
+    let src_rgb = src.to_rgb8();
+    let dst_rgb = dst.to_rgb8();
+    
+    let (width, height) = dst_rgb.dimensions();
+    let mut mask = GrayImage::new(width, height);
+    
+    for (x, y, dst_pix) in dst_rgb.enumerate_pixels() {
+        let src_pix = src_rgb.get_pixel(x, y);
+        if src_pix == dst_pix {
+            continue;
+        }
+        
+        let p = to_lab(*src_pix);
+        let q = to_lab(*dst_pix);
+        
+        let delta = match mode {
+            DiffMode::DeltaE94   => delta_e94(&p, &q),
+            DiffMode::DeltaE2000 => delta_e2000(&p, &q),
+            _                    => delta_e76(&p, &q),
+        };
+        
+        if delta > thresh {
+            let contrast = delta.round().clamp(1.0, 255.0) as u8;
+            mask.put_pixel(x, y, Luma([contrast]));
+        }
+    }
+    
+    return Ok(mask);
+}
+
+/// Applies a morphological operation to a contrast mask.
+/// Pixels added by the operation get the lowest non-zero contrast.
+pub fn morph_mask(mask: &GrayImage, op: MorphOp, kernel: Kernel, radius: u8) -> GrayImage {
+    let binary = threshold(mask, 0);
+    let norm = kernel.norm();
+    
+    let shaped = match op {
+        MorphOp::None   => return mask.clone(),
+        MorphOp::Erode  => erode(&binary, norm, radius),
+        MorphOp::Dilate => dilate(&binary, norm, radius),
+        MorphOp::Open   => open_morph(&binary, norm, radius),
+        MorphOp::Close  => close(&binary, norm, radius),
+    };
+    
+    let dst = map_pixels(&shaped, |x, y, p: Luma<u8>| {
+        if p[0] == 0 {
+            return Luma([0u8]);
+        }
+        return Luma([mask.get_pixel(x, y)[0].max(1)]);
+    });
+    
+    return dst;
+}
+
+/// Removes connected regions smaller than the minimum area from a contrast mask.
+pub fn remove_small(mask: &GrayImage, min_area: u32) -> GrayImage {
+    let mut dst = mask.clone();
+    if min_area <= 1 {
+        return dst;
+    }
+    
+    let labels = connected_components(&threshold(mask, 0), Connectivity::Eight, Luma([0u8]));
+    let mut areas: Vec<u32> = Vec::new();
+    
+    for label in labels.pixels() {
+        let id = label[0] as usize;
+        while areas.len() <= id {
+            areas.push(0);
+        }
+        areas[id] += 1;
+    }
+    
+    for (x, y, label) in labels.enumerate_pixels() {
+        if label[0] > 0 && areas[label[0] as usize] < min_area {
+            dst.put_pixel(x, y, Luma([0u8]));
+        }
+    }
+    
+    return dst;
+}
+
+/// Clears the pixels of a contrast mask outside a region-of-interest mask.
+pub fn apply_mask(mask: &GrayImage, roi: &GrayImage) -> Result<GrayImage> {
+    check_dimensions(mask.dimensions(), roi.dimensions())?;
+    
+    let mut dst = mask.clone();
+    
+    for (x, y, pix) in dst.enumerate_pixels_mut() {
+        if roi.get_pixel(x, y)[0] == 0 {
+            *pix = Luma([0u8]);
+        }
+    }
+    
+    return Ok(dst);
+}
+
+/// Marks the pixels flagged in a contrast mask with the given color.
+pub fn mark_mask<'a>(dst: &'a mut DynamicImage, mask: &GrayImage, color: Rgb<u8>)
+-> Result<(&'a DynamicImage, Vec<Point>, u32, u32, u32)> {
+    
+    check_dimensions(dst.dimensions(), mask.dimensions())?;
+    
+    let mut points = Vec::new();
+    
+    let mut counter: u32 = 0;
+    let (width, height) = dst.dimensions();
+
+    for (x, y, pix) in mask.enumerate_pixels() {
+        if pix[0] > 0 {
+            dst.put_pixel(x, y, Rgba([color[0], color[1], color[2], 0]));
+            let pt = Point{ x: x as i32, y: y as i32 };
+            points.push(pt);
+            counter += 1;
+        }
+    }
+
+    return Ok((dst, points, width, height, counter));
+}
+
+/// Splits a contrast mask into connected defect regions.
+pub fn segment(mask: &GrayImage) -> Vec<Defect> {
+    // label 8-connected regions of the binarized mask
+    let binary = threshold(mask, 0);
+    let labels = connected_components(&binary, Connectivity::Eight, Luma([0u8]));
+    
+    let mut defects: Vec<Defect> = Vec::new();
+    let mut sums: Vec<(u64, u64, u64)> = Vec::new();
+    let mut bounds: Vec<(u32, u32, u32, u32)> = Vec::new();
+    let mut pixels: Vec<Vec<(u32, u32)>> = Vec::new();
+    
+    for (x, y, label) in labels.enumerate_pixels() {
+        let id = label[0];
+        if id == 0 {
+            continue;
+        }
+        
+        let index = (id - 1) as usize;
+        let contrast = mask.get_pixel(x, y)[0];
+        
+        // create a new record the first time a label shows up
+        while defects.len() <= index {
+            let defect = Defect{
+                id: defects.len() as u32 + 1,
+                rect: Rect::at(0, 0).of_size(1, 1),
+                area: 0,
+                centroid: (0.0, 0.0),
+                mean_contrast: 0.0,
+                max_contrast: 0,
+                zone: None,
+                features: Features::default(),
+                class: DefectClass::Spot,
+            };
+            defects.push(defect);
+            pixels.push(Vec::new());
+            sums.push((0, 0, 0));
+            bounds.push((u32::MAX, u32::MAX, 0, 0));
+        }
+        
+        let defect = &mut defects[index];
+        defect.area += 1;
+        
+        if contrast > defect.max_contrast {
+            defect.max_contrast = contrast;
+        }
+        
+        pixels[index].push((x, y));
+        
+        let sum = &mut sums[index];
+        sum.0 += x as u64;
+        sum.1 += y as u64;
+        sum.2 += contrast as u64;
+        
+        let bound = &mut bounds[index];
+        bound.0 = bound.0.min(x);
+        bound.1 = bound.1.min(y);
+        bound.2 = bound.2.max(x);
+        bound.3 = bound.3.max(y);
+    }
+    
+    // derive boxes and averages from accumulated sums, then classify by shape
+    let (width, height) = mask.dimensions();
+    for (index, defect) in defects.iter_mut().enumerate() {
+        if defect.area == 0 {
+            continue;
+        }
+        
+        let (left, top, right, bottom) = bounds[index];
+        let (sum_x, sum_y, sum_c) = sums[index];
+        let area = defect.area as f32;
+        
+        defect.rect = Rect::at(left as i32, top as i32).of_size(right - left + 1, bottom - top + 1);
+        defect.centroid = (sum_x as f32 / area, sum_y as f32 / area);
+        defect.mean_contrast = sum_c as f32 / area;
+        defect.features = classify::measure(&pixels[index], &labels);
+        
+        let on_border = left == 0 || top == 0 || right + 1 >= width || bottom + 1 >= height;
+        defect.class = classify::classify(&defect.features, defect.area, defect.mean_contrast, on_border);
+    }
+    
+    // drop labels that had no pixels
+    defects.retain(|defect| defect.area > 0);
+    
+    return defects;
+}
+
+/// Draws one bounding box around each defect, colored by defect class.
+pub fn draw_rects(src: &DynamicImage, defects: &Vec<Defect>) -> DynamicImage {
+    let mut bound: RgbaImage = src.to_rgba8();
+    
+    for defect in defects.iter() {
+        // increase/decrease edge points by 1
+        let rect = Rect::at(defect.rect.left() - 1, defect.rect.top() - 1)
+            .of_size(defect.rect.width() + 2, defect.rect.height() + 2);
+        draw_hollow_rect_mut(&mut bound, rect, defect.class.color());
+    }
+    
+    let dst = rgba_to_dynamic(bound);
+    return dst;
+}
+
+/// Converts RgbaImage buffer to DynamicImage buffer.
+pub fn rgba_to_dynamic(src: RgbaImage) -> DynamicImage {
+    let dst = DynamicImage::ImageRgba8(src);
+    return dst;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    /// Creates a mask with the given (x, y, contrast) pixels set.
+    fn mask_of(width: u32, height: u32, pixels: &[(u32, u32, u8)]) -> GrayImage {
+        let mut mask = GrayImage::new(width, height);
+        for &(x, y, contrast) in pixels.iter() {
+            mask.put_pixel(x, y, Luma([contrast]));
+        }
+        return mask;
+    }
+
+    #[test]
+    fn segment_joins_diagonal_neighbours() {
+        let mask = mask_of(10, 10, &[(2, 2, 50), (3, 3, 100)]);
+        let defects = segment(&mask);
+
+        assert_eq!(defects.len(), 1);
+        assert_eq!(defects[0].area, 2);
+        assert_eq!(defects[0].rect, Rect::at(2, 2).of_size(2, 2));
+        assert_eq!(defects[0].max_contrast, 100);
+        assert_eq!(defects[0].mean_contrast, 75.0);
+        assert_eq!(defects[0].centroid, (2.5, 2.5));
+    }
+
+    #[test]
+    fn segment_splits_separate_blobs() {
+        let mask = mask_of(12, 12, &[
+            (1, 1, 40),
+            (5, 6, 30), (6, 6, 30), (5, 7, 30), (6, 7, 30),
+            (9, 2, 20), (9, 3, 20), (9, 4, 20),
+        ]);
+        let mut defects = segment(&mask);
+        defects.sort_by_key(|defect| defect.area);
+
+        let areas: Vec<u32> = defects.iter().map(|defect| defect.area).collect();
+        assert_eq!(areas, vec![1, 3, 4]);
+        assert_eq!(defects[1].rect, Rect::at(9, 2).of_size(1, 3));
+        assert_eq!(defects[2].rect, Rect::at(5, 6).of_size(2, 2));
+
+        // ids number the defects consecutively
+        let mut ids: Vec<u32> = defects.iter().map(|defect| defect.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn segment_finds_nothing_in_empty_mask() {
+        assert!(segment(&GrayImage::new(8, 8)).is_empty());
+    }
+
+    /// Renders a blocky pseudo-random texture.
+    fn texture(width: u32, height: u32) -> GrayImage {
+        return GrayImage::from_fn(width, height, |x, y| {
+            let cell = (x / 4 * 7919 + y / 4 * 104729) as u64;
+            Luma([(cell.wrapping_mul(2654435761) >> 7) as u8])
+        });
+    }
+
+    #[test]
+    fn ssim_of_identical_images_is_one() {
+        let src = texture(40, 30);
+        let (index, map) = ssim(&src, &src, 3).unwrap();
+
+        assert!((index - 1.0).abs() < 1e-6, "{}", index);
+        assert!(map.pixels().all(|p| p[0] == 0));
+    }
+
+    #[test]
+    fn ssim_drops_for_shifted_images() {
+        let src = texture(40, 30);
+        let dst = GrayImage::from_fn(40, 30, |x, y| *src.get_pixel((x + 2) % 40, y));
+        let (index, map) = ssim(&src, &dst, 3).unwrap();
+
+        assert!(index < 0.8, "{}", index);
+        assert!(threshold_mask(&map, 10).pixels().any(|p| p[0] > 0));
+    }
+
+    #[test]
+    fn ssim_rejects_different_sizes() {
+        let result = ssim(&texture(40, 30), &texture(30, 40), 3);
+        assert!(matches!(result, Err(Error::DimensionMismatch { .. })));
+    }
+
+    /// CIEDE2000 test pairs of Sharma, Wu and Dalal (2005) with their expected differences.
+    const SHARMA_PAIRS: [([f32; 3], [f32; 3], f32); 34] = [
+        ([50.0, 2.6772, -79.7751], [50.0, 0.0, -82.7485], 2.0425),
+        ([50.0, 3.1571, -77.2803], [50.0, 0.0, -82.7485], 2.8615),
+        ([50.0, 2.8361, -74.0200], [50.0, 0.0, -82.7485], 3.4412),
+        ([50.0, -1.3802, -84.2814], [50.0, 0.0, -82.7485], 1.0000),
+        ([50.0, -1.1848, -84.8006], [50.0, 0.0, -82.7485], 1.0000),
+        ([50.0, -0.9009, -85.5211], [50.0, 0.0, -82.7485], 1.0000),
+        ([50.0, 0.0, 0.0], [50.0, -1.0, 2.0], 2.3669),
+        ([50.0, -1.0, 2.0], [50.0, 0.0, 0.0], 2.3669),
+        ([50.0, 2.4900, -0.0010], [50.0, -2.4900, 0.0009], 7.1792),
+        ([50.0, 2.4900, -0.0010], [50.0, -2.4900, 0.0010], 7.1792),
+        ([50.0, 2.4900, -0.0010], [50.0, -2.4900, 0.0011], 7.2195),
+        ([50.0, 2.4900, -0.0010], [50.0, -2.4900, 0.0012], 7.2195),
+        ([50.0, -0.0010, 2.4900], [50.0, 0.0009, -2.4900], 4.8045),
+        ([50.0, -0.0010, 2.4900], [50.0, 0.0010, -2.4900], 4.8045),
+        ([50.0, -0.0010, 2.4900], [50.0, 0.0011, -2.4900], 4.7461),
+        ([50.0, 2.5, 0.0], [50.0, 0.0, -2.5], 4.3065),
+        ([50.0, 2.5, 0.0], [73.0, 25.0, -18.0], 27.1492),
+        ([50.0, 2.5, 0.0], [61.0, -5.0, 29.0], 22.8977),
+        ([50.0, 2.5, 0.0], [56.0, -27.0, -3.0], 31.9030),
+        ([50.0, 2.5, 0.0], [58.0, 24.0, 15.0], 19.4535),
+        ([50.0, 2.5, 0.0], [50.0, 3.1736, 0.5854], 1.0000),
+        ([50.0, 2.5, 0.0], [50.0, 3.2972, 0.0], 1.0000),
+        ([50.0, 2.5, 0.0], [50.0, 1.8634, 0.5757], 1.0000),
+        ([50.0, 2.5, 0.0], [50.0, 3.2592, 0.3350], 1.0000),
+        ([60.2574, -34.0099, 36.2677], [60.4626, -34.1751, 39.4387], 1.2644),
+        ([63.0109, -31.0961, -5.8663], [62.8187, -29.7946, -4.0864], 1.2630),
+        ([61.2901, 3.7196, -5.3901], [61.4292, 2.2480, -4.9620], 1.8731),
+        ([35.0831, -44.1164, 3.7933], [35.0232, -40.0716, 1.5901], 1.8645),
+        ([22.7233, 20.0904, -46.6940], [23.0331, 14.9730, -42.5619], 2.0373),
+        ([36.4612, 47.8580, 18.3852], [36.2715, 50.5065, 21.2231], 1.4146),
+        ([90.8027, -2.0831, 1.4410], [91.1528, -1.6435, 0.0447], 1.4441),
+        ([90.9257, -0.5406, -0.9208], [88.6381, -0.8985, -0.7239], 1.5381),
+        ([6.7747, -0.2908, -2.4247], [5.8714, -0.0985, -2.2286], 0.6377),
+        ([2.0776, 0.0795, -1.1350], [0.9033, -0.0636, -0.5514], 0.9082),
+    ];
+
+    #[test]
+    fn delta_e2000_matches_sharma_pairs() {
+        for (i, (p, q, expected)) in SHARMA_PAIRS.iter().enumerate() {
+            let found = delta_e2000(p, q);
+            assert!((found - expected).abs() < 1e-4, "pair {}: {} != {}", i + 1, found, expected);
+
+            // the difference is symmetric
+            assert!((delta_e2000(q, p) - found).abs() < 1e-4, "pair {} is not symmetric", i + 1);
+        }
+    }
+
+    #[test]
+    fn delta_e_of_equal_colors_is_zero() {
+        let lab = to_lab(Rgb([200, 120, 40]));
+        assert_eq!(delta_e76(&lab, &lab), 0.0);
+        assert_eq!(delta_e94(&lab, &lab), 0.0);
+        assert_eq!(delta_e2000(&lab, &lab), 0.0);
+    }
+
+    /// Creates a flat gray image with given (left, top, size, level) squares drawn onto it.
+    fn flat_with(width: u32, height: u32, squares: &[(u32, u32, u32, u8)]) -> GrayImage {
+        let mut src = GrayImage::from_pixel(width, height, Luma([100]));
+        for &(left, top, size, level) in squares.iter() {
+            for y in top..(top + size) {
+                for x in left..(left + size) {
+                    src.put_pixel(x, y, Luma([level]));
+                }
+            }
+        }
+        return src;
+    }
+
+    #[test]
+    fn spot_mask_finds_bright_and_dark_spots() {
+        let src = flat_with(60, 60, &[(14, 14, 3, 220), (44, 44, 3, 10)]);
+        let mut defects = segment(&spot_mask(&src, 5, 10));
+        defects.sort_by_key(|defect| defect.rect.left());
+
+        assert_eq!(defects.len(), 2);
+        assert_eq!(defects[0].centroid, (15.0, 15.0));
+        assert_eq!(defects[1].centroid, (45.0, 45.0));
+        assert_eq!(spot_polarity(&src, &defects, 5), (1, 1));
+
+        defects.truncate(1);
+        assert_eq!(spot_polarity(&src, &defects, 5), (1, 0));
+    }
+
+    #[test]
+    fn spot_mask_rejects_large_blobs() {
+        let src = flat_with(60, 60, &[(14, 14, 3, 220), (30, 30, 15, 220)]);
+        let defects = segment(&spot_mask(&src, 5, 10));
+
+        // the square is wider than the 11 px window, only the small spot remains
+        assert_eq!(defects.len(), 1);
+        assert_eq!(defects[0].centroid, (15.0, 15.0));
+    }
+
+    /// Counts the non-zero pixels of a mask.
+    fn count(mask: &GrayImage) -> usize {
+        return mask.pixels().filter(|pix| pix[0] > 0).count();
+    }
+
+    #[test]
+    fn opening_removes_specks() {
+        let mut mask = mask_of(12, 12, &[(9, 9, 80)]);
+        for y in 2..5 {
+            for x in 2..5 {
+                mask.put_pixel(x, y, Luma([60]));
+            }
+        }
+        let opened = morph_mask(&mask, MorphOp::Open, Kernel::Square, 1);
+
+        assert_eq!(opened.get_pixel(9, 9)[0], 0);
+        assert_eq!(count(&opened), 9);
+        assert_eq!(opened.get_pixel(3, 3)[0], 60);
+    }
+
+    #[test]
+    fn closing_fills_gaps() {
+        let mut mask = GrayImage::new(12, 12);
+        for y in 2..5 {
+            for x in (2..5).chain(6..9) {
+                mask.put_pixel(x, y, Luma([60]));
+            }
+        }
+        let closed = morph_mask(&mask, MorphOp::Close, Kernel::Square, 1);
+
+        // added pixels get the lowest contrast
+        assert_eq!(closed.get_pixel(5, 3)[0], 1);
+        assert_eq!(closed.get_pixel(4, 3)[0], 60);
+        assert_eq!(count(&closed), 21);
+    }
+
+    #[test]
+    fn morph_honours_kernel_shape() {
+        let mask = mask_of(9, 9, &[(4, 4, 50)]);
+
+        assert_eq!(count(&morph_mask(&mask, MorphOp::Dilate, Kernel::Square, 1)), 9);
+        assert_eq!(count(&morph_mask(&mask, MorphOp::Dilate, Kernel::Diamond, 1)), 5);
+        assert_eq!(count(&morph_mask(&mask, MorphOp::Dilate, Kernel::Diamond, 2)), 13);
+        assert_eq!(morph_mask(&mask, MorphOp::None, Kernel::Square, 1), mask);
+    }
+
+    #[test]
+    fn remove_small_keeps_min_area() {
+        let mask = mask_of(12, 12, &[
+            (1, 1, 40), (2, 2, 40), (3, 3, 40),
+            (8, 1, 40), (8, 2, 40),
+        ]);
+        let kept = remove_small(&mask, 3);
+
+        assert_eq!(count(&kept), 3);
+        assert_eq!(kept.get_pixel(2, 2)[0], 40);
+        assert_eq!(kept.get_pixel(8, 1)[0], 0);
+        assert_eq!(remove_small(&mask, 1), mask);
+    }
+}
@@ -0,0 +1,348 @@
+//! Glassvis image diff engine for visual quality inspection of panel glasses.
+//!
+//! The top-level functions cover a complete inspection: `open` the reference and
+//! captured images, `compare` them (or `detect` hotspots in a single image),
+//! `annotate` the captured image with the found defects and `save` the result.
+//! Lower level building blocks live in the `imgfx` and `align` modules, inspected
+//! areas are limited with `roi` masks and graded per `zone`, defects are sorted by
+//! shape in `classify`, named per-product settings are stored as `recipe` files,
+//! inspections are judged against the recipe limits into a `verdict`, and results
+//! are exported as a `report` and recorded in the inspection `history`. Captured
+//! images can be grabbed from a camera or test pattern through a frame `source`
+//! (several frames can be merged into one lower-noise capture with `stack`), or
+//! picked up from a `watch`ed hot folder, and whole directories of captures are
+//! inspected as a `batch`.
+
+use image::{DynamicImage, GrayImage, Rgb};
+use serde::{Deserialize, Serialize};
+
+pub mod align;
+pub mod aux;
+pub mod batch;
+pub mod classify;
+pub mod error;
+pub mod history;
+pub mod html;
+pub mod imgfx;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod recipe;
+pub mod report;
+pub mod roi;
+pub mod source;
+pub mod stack;
+pub mod verdict;
+pub mod watch;
+pub mod zone;
+
+pub use align::Alignment;
+pub use batch::{Batch, References, Summary};
+pub use classify::{DefectClass, Features};
+pub use error::{Error, Result};
+pub use history::History;
+pub use imgfx::{Defect, DiffMode, Filter, Kernel, MorphOp};
+pub use recipe::{Output, Recipe};
+pub use report::Report;
+pub use roi::{Roi, Shape};
+pub use source::FrameSource;
+pub use verdict::{Finding, Judgement, Limits, Verdict};
+pub use watch::HotFolder;
+pub use zone::{Grade, Zone};
+
+/// Represents the settings of an inspection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub mode: DiffMode,
+    pub significance: u8,
+    pub delta_e: f32,
+    pub align: bool,
+    pub spot_radius: u32,
+    /// Radius of the SSIM comparison window (SSIM mode only).
+    pub ssim_radius: u32,
+    pub filter: Filter,
+    /// Inclusion and exclusion masks limiting the inspected pixels.
+    pub roi: Roi,
+    /// Zones with their own tolerances, graded separately.
+    pub zones: Vec<Zone>,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        return Settings{
+            mode: DiffMode::Luma,
+            significance: 10,
+            delta_e: 2.3,
+            align: false,
+            spot_radius: 8,
+            ssim_radius: 3,
+            filter: Filter::default(),
+            roi: Roi::default(),
+            zones: Vec::new(),
+        };
+    }
+}
+
+impl Settings {
+    /// Checks that the global and zone tolerances are in range (a zero significance would divide by zero).
+    pub fn validate(&self) -> Result<()> {
+        if self.significance == 0 || self.significance > 100 {
+            return Err(Error::Recipe(format!("significance {} is out of range 1-100", self.significance)));
+        }
+        if self.delta_e.is_finite() == false || self.delta_e <= 0.0 {
+            return Err(Error::Recipe(format!("delta_e {} must be positive", self.delta_e)));
+        }
+        if self.ssim_radius == 0 {
+            return Err(Error::Recipe(String::from("ssim_radius must be at least 1")));
+        }
+        for zone in self.zones.iter() {
+            zone.validate()?;
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the settings with the ROI and zone shapes scaled by the given factors,
+    /// for inspecting a resized image.
+    pub fn scaled(&self, sx: f64, sy: f64) -> Settings {
+        let mut settings = self.clone();
+        settings.roi = self.roi.scaled(sx, sy);
+
+        for zone in settings.zones.iter_mut() {
+            zone.region = zone.region.as_ref().map(|shape| shape.scaled(sx, sy));
+        }
+
+        return settings;
+    }
+}
+
+/// Represents the result of an inspection.
+pub struct Inspection {
+    /// Inspected image (the captured image after alignment, if enabled).
+    pub image: DynamicImage,
+    /// Filtered contrast mask of defect pixels.
+    pub mask: GrayImage,
+    /// Connected defect regions found in the mask.
+    pub defects: Vec<Defect>,
+    /// Number of defect pixels.
+    pub counter: u32,
+    /// Number of inspected pixels (inside the region of interest and the aligned capture).
+    pub area: u32,
+    /// Grading results of the zones (zone grading only).
+    pub grades: Vec<Grade>,
+    /// Global SSIM index (SSIM mode only).
+    pub score: Option<f32>,
+    /// Estimated capture-to-reference transform (alignment only).
+    pub alignment: Option<Alignment>,
+    /// Reason the alignment failed, the captured image is inspected unaligned.
+    pub alignment_error: Option<String>,
+}
+
+impl Inspection {
+    /// Returns the inspected image dimensions.
+    pub fn dimensions(&self) -> (u32, u32) {
+        return self.mask.dimensions();
+    }
+
+    /// Returns the percentage of defect pixels among the inspected pixels.
+    pub fn defect_rate(&self) -> f32 {
+        if self.area == 0 {
+            return 0.0;
+        }
+
+        let rate = self.counter as f32 * 100.0 / self.area as f32;
+        return rate;
+    }
+
+    /// Checks if every zone holds no more defects than allowed.
+    pub fn zones_passed(&self) -> bool {
+        return self.grades.iter().all(|grade| grade.passed());
+    }
+
+    /// Returns the number of defects of each class found, in class order.
+    pub fn class_counts(&self) -> Vec<(DefectClass, u32)> {
+        let counts = DefectClass::ALL.iter()
+            .map(|class| (*class, self.defects.iter().filter(|defect| defect.class == *class).count() as u32))
+            .filter(|(_, count)| *count > 0)
+            .collect();
+
+        return counts;
+    }
+}
+
+/// Creates a dynamic image buffer from a specified image file.
+pub fn open(img_file: &str) -> Result<DynamicImage> {
+    return imgfx::open(img_file);
+}
+
+/// Saves an image to an image file.
+pub fn save(src: &DynamicImage, img_file: &str) -> Result<()> {
+    return imgfx::save(src, img_file);
+}
+
+/// Splits a contrast mask into connected defect regions.
+pub fn segment(mask: &GrayImage) -> Vec<Defect> {
+    return imgfx::segment(mask);
+}
+
+/// Compares a captured image with a reference image.
+pub fn compare(src: &DynamicImage, dst: &DynamicImage, settings: &Settings) -> Result<Inspection> {
+    settings.validate()?;
+
+    let mut image = dst.clone();
+    let mut alignment = None;
+    let mut coverage = None;
+    let mut alignment_error = None;
+
+    // register captured image onto reference image
+    if settings.align {
+        match align::align(src, dst) {
+            Ok((warped, covered, found)) => {
+                image = warped;
+                coverage = Some(covered);
+                alignment = Some(found);
+            }
+            Err(err) => alignment_error = Some(err.to_string()),
+        }
+    }
+
+    // find diff pixels with the selected mode, as loose as the most sensitive zone
+    let (significance, delta_e) = zone::loosest(settings);
+    let mut score = None;
+    let mask = match settings.mode {
+        DiffMode::Luma => imgfx::diff_mask(src, &image, significance)?,
+        DiffMode::Ssim => {
+            let (index, map) = imgfx::ssim(&src.to_luma8(), &image.to_luma8(), settings.ssim_radius)?;
+            score = Some(index);
+            imgfx::threshold_mask(&map, significance)
+        }
+        _ => imgfx::color_mask(src, &image, settings.mode, delta_e)?,
+    };
+
+    let mut inspection = finish(image, mask, settings.mode, score, alignment, coverage, settings)?;
+    inspection.alignment_error = alignment_error;
+
+    return Ok(inspection);
+}
+
+/// Detects isolated bright or dark spots in a single image.
+pub fn detect(src: &DynamicImage, settings: &Settings) -> Result<Inspection> {
+    settings.validate()?;
+
+    let (significance, _) = zone::loosest(settings);
+    let luma = src.to_luma8();
+    let mask = imgfx::spot_mask(&luma, settings.spot_radius, significance);
+
+    return finish(src.clone(), mask, DiffMode::Luma, None, None, None, settings);
+}
+
+/// Masks, filters, grades and segments a raw contrast mask into an inspection result.
+fn finish(
+    image: DynamicImage,
+    mask: GrayImage,
+    mode: DiffMode,
+    score: Option<f32>,
+    alignment: Option<Alignment>,
+    coverage: Option<GrayImage>,
+    settings: &Settings,
+) -> Result<Inspection> {
+    // ignore diff pixels outside the region of interest and outside the warped capture
+    let (width, height) = mask.dimensions();
+    let mut roi = settings.roi.render(width, height)?;
+    if let Some(coverage) = &coverage {
+        roi = imgfx::apply_mask(&roi, coverage)?;
+    }
+    let mask = imgfx::apply_mask(&mask, &roi)?;
+    let area = roi.pixels().filter(|p| p[0] > 0).count() as u32;
+
+    // apply the tolerances of each zone
+    let map = zone::render(&settings.zones, width, height);
+    let mask = zone::threshold(&mask, &map, settings, mode);
+
+    // suppress noise before counting defects (dilation may grow back into excluded areas)
+    let filter = &settings.filter;
+    let mask = imgfx::morph_mask(&mask, filter.op, filter.kernel, filter.radius);
+    let mask = zone::remove_small(&mask, &map, settings);
+    let mask = imgfx::apply_mask(&mask, &roi)?;
+    let mut defects = imgfx::segment(&mask);
+    let counter = mask.pixels().filter(|p| p[0] > 0).count() as u32;
+
+    for defect in defects.iter_mut() {
+        let index = zone::locate(defect, &map);
+        if index > 0 {
+            defect.zone = Some(settings.zones[index - 1].name.clone());
+        }
+    }
+
+    let grades = zone::grade(settings, &map, &roi, &mask, &defects);
+
+    let inspection = Inspection{
+        image: image,
+        mask: mask,
+        defects: defects,
+        counter: counter,
+        area: area,
+        grades: grades,
+        score: score,
+        alignment: alignment,
+        alignment_error: None,
+    };
+
+    return Ok(inspection);
+}
+
+/// Marks the defect pixels of an inspection, optionally boxing each defect,
+/// and outlines its zones. Fails if the mask does not match the inspected image.
+pub fn annotate(inspection: &Inspection, output: &Output) -> Result<DynamicImage> {
+    let mut dst = inspection.image.clone();
+    let marker = Rgb(output.marker);
+
+    imgfx::mark_mask(&mut dst, &inspection.mask, marker)?;
+
+    if output.bbox {
+        dst = imgfx::draw_rects(&dst, &inspection.defects);
+    }
+
+    if inspection.grades.is_empty() == false {
+        let mut zoned = dst.to_rgba8();
+        zone::draw(&mut zoned, &inspection.grades);
+        dst = DynamicImage::ImageRgba8(zoned);
+    }
+
+    return Ok(dst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::imageops::FilterType;
+
+    use crate::report::Input;
+    use crate::source::{FrameSource, SyntheticSource};
+    use crate::verdict::{Limits, Verdict};
+
+    #[test]
+    fn live_frames_are_inspected_against_a_clean_reference() {
+        let reference = SyntheticSource::new(120, 80).grab().unwrap();
+        let mut source = SyntheticSource::new(240, 160);
+        source.defects = true;
+        let settings = Settings::default();
+        let limits = Limits{ max_rate: Some(0.0), ..Limits::default() };
+
+        // live frames are fitted to the reference before they are compared
+        let mut hashes = Vec::new();
+        for _ in 0..2 {
+            let frame = source.grab().unwrap().resize_exact(120, 80, FilterType::CatmullRom);
+            let inspection = compare(&reference, &frame, &settings).unwrap();
+            let judgement = verdict::judge(&inspection, &settings, &limits);
+
+            assert!(inspection.defects.is_empty() == false);
+            assert_eq!(judgement.verdict, Verdict::Fail);
+            hashes.push(Input::from_frame(&source.name(), &frame).sha256);
+        }
+        assert_ne!(hashes[0], hashes[1]);
+
+        let clean = compare(&reference, &reference, &settings).unwrap();
+        assert_eq!(verdict::judge(&clean, &settings, &limits).verdict, Verdict::Pass);
+    }
+}
@@ -0,0 +1,320 @@
+//! PDF report module for Glassvis (requires the "pdf" feature).
+//!
+//! Renders an inspection report as a printable A4 certificate: the verdict, the
+//! inputs, a summary, the reference, captured and marked images, the settings
+//! and zone grades, followed by a defect table with a crop of each defect.
+
+use cairo::{Context, FontSlant, FontWeight, Format, ImageSurface, PdfSurface};
+use image::{DynamicImage, GenericImageView};
+
+use crate::error::{Error, Result};
+use crate::report::{self, Images, Report};
+use crate::verdict::Verdict;
+
+/// A4 page width in points.
+const PAGE_WIDTH: f64 = 595.0;
+
+/// A4 page height in points.
+const PAGE_HEIGHT: f64 = 842.0;
+
+/// Page margin in points.
+const MARGIN: f64 = 40.0;
+
+/// Height of a defect table row (fits a crop) in points.
+const ROW_HEIGHT: f64 = 46.0;
+
+/// Defect table columns as (title, x offset).
+const COLUMNS: [(&str, f64); 9] = [
+    ("Crop", 0.0), ("ID", 52.0), ("Class", 82.0), ("X", 148.0), ("Y", 188.0),
+    ("Size", 228.0), ("Area", 290.0), ("Contrast", 340.0), ("Zone", 410.0),
+];
+
+/// Represents the write position on the current page.
+struct Page {
+    ctx: Context,
+    y: f64,
+    number: u32,
+}
+
+impl Page {
+    /// Starts a new page if the next block of given height does not fit.
+    fn reserve(&mut self, height: f64) {
+        if self.y + height > PAGE_HEIGHT - MARGIN {
+            self.next();
+        }
+    }
+
+    /// Finishes the current page and starts a new one.
+    fn next(&mut self) {
+        footer(&self.ctx, self.number);
+        self.ctx.show_page();
+        self.number += 1;
+        self.y = MARGIN;
+    }
+
+    /// Writes a line of text and advances by its height.
+    fn line(&mut self, text: &str, size: f64, bold: bool) {
+        self.reserve(size * 1.5);
+        self.y += size * 1.2;
+        show_text(&self.ctx, MARGIN, self.y, size, bold, text);
+        self.y += size * 0.3;
+    }
+
+    /// Writes a section heading.
+    fn heading(&mut self, text: &str) {
+        self.reserve(40.0);
+        self.y += 10.0;
+        self.line(text, 12.0, true);
+        self.y += 4.0;
+    }
+}
+
+/// Writes text with its baseline at the given position.
+fn show_text(ctx: &Context, x: f64, y: f64, size: f64, bold: bool, text: &str) {
+    let weight = if bold { FontWeight::Bold } else { FontWeight::Normal };
+
+    ctx.set_source_rgb(0.10, 0.10, 0.10);
+    ctx.select_font_face("Sans", FontSlant::Normal, weight);
+    ctx.set_font_size(size);
+    ctx.move_to(x, y);
+    ctx.show_text(text);
+}
+
+/// Writes the page number at the bottom of a page.
+fn footer(ctx: &Context, number: u32) {
+    let text = format!("Page {}", number);
+    show_text(ctx, PAGE_WIDTH - MARGIN - 40.0, PAGE_HEIGHT - MARGIN / 2.0, 8.0, false, &text);
+}
+
+/// Converts an image into a cairo image surface.
+fn to_surface(src: &DynamicImage) -> Result<ImageSurface> {
+    let rgba = src.to_rgba8();
+    let (width, height) = rgba.dimensions();
+    let stride = width as i32 * 4;
+
+    // cairo stores RGB24 pixels as native-endian 32-bit words (BGRX in memory)
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+    for pix in rgba.pixels() {
+        data.extend_from_slice(&[pix[2], pix[1], pix[0], 255]);
+    }
+
+    let surface = ImageSurface::create_for_data(data, Format::Rgb24, width as i32, height as i32, stride)
+        .map_err(|err| Error::Report(err.to_string()))?;
+
+    return Ok(surface);
+}
+
+/// Draws an image scaled to fit into a box, keeping its aspect ratio, and frames it.
+fn draw_image(ctx: &Context, src: &DynamicImage, x: f64, y: f64, width: f64, height: f64) -> Result<()> {
+    let (w, h) = src.dimensions();
+    if w == 0 || h == 0 {
+        return Ok(());
+    }
+
+    let surface = to_surface(src)?;
+    let scale = (width / w as f64).min(height / h as f64);
+
+    ctx.save();
+    ctx.translate(x, y);
+    ctx.scale(scale, scale);
+    ctx.set_source_surface(&surface, 0.0, 0.0);
+    ctx.paint();
+    ctx.restore();
+
+    ctx.set_source_rgb(0.60, 0.60, 0.60);
+    ctx.set_line_width(0.5);
+    ctx.rectangle(x, y, w as f64 * scale, h as f64 * scale);
+    ctx.stroke();
+
+    return Ok(());
+}
+
+/// Draws the title and the verdict badge.
+fn draw_title(page: &mut Page, report: &Report) {
+    let ctx = &page.ctx;
+    let (r, g, b) = match report.verdict {
+        Verdict::Pass   => (0.20, 0.60, 0.30),
+        Verdict::Review => (0.90, 0.54, 0.00),
+        Verdict::Fail   => (0.80, 0.20, 0.20),
+    };
+
+    show_text(ctx, MARGIN, MARGIN + 18.0, 18.0, true, "Glassvis Inspection Report");
+
+    ctx.set_source_rgb(r, g, b);
+    ctx.rectangle(PAGE_WIDTH - MARGIN - 100.0, MARGIN, 100.0, 30.0);
+    ctx.fill();
+
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.select_font_face("Sans", FontSlant::Normal, FontWeight::Bold);
+    ctx.set_font_size(16.0);
+    let extents = ctx.text_extents(report.verdict.name());
+    ctx.move_to(PAGE_WIDTH - MARGIN - 50.0 - extents.width / 2.0, MARGIN + 21.0);
+    ctx.show_text(report.verdict.name());
+
+    page.y = MARGIN + 30.0;
+    page.line(&format!("Inspected: {}", report.timestamp), 9.0, false);
+
+    for finding in report.findings.iter() {
+        page.line(&format!("{}: {}", finding.verdict.name(), finding.rule), 9.0, false);
+    }
+}
+
+/// Writes the input files along with their hashes.
+fn draw_inputs(page: &mut Page, report: &Report) {
+    page.heading("Inputs");
+
+    let mut inputs = vec![("Capture", &report.capture)];
+    if let Some(reference) = &report.reference {
+        inputs.insert(0, ("Reference", reference));
+    }
+
+    for (name, input) in inputs {
+        page.line(&format!("{}: {}", name, input.path), 9.0, false);
+        page.line(&format!("    SHA-256: {}", input.sha256), 7.0, false);
+    }
+}
+
+/// Writes the inspection summary.
+fn draw_summary(page: &mut Page, report: &Report) {
+    page.heading("Summary");
+    page.line(&format!("Image size: {} x {} px", report.width, report.height), 9.0, false);
+    page.line(&format!("Defects: {}", report.defect_count), 9.0, false);
+    page.line(&format!("Defect rate: {:.4}%  ({} px)", report.defect_rate, report.counter), 9.0, false);
+
+    if let Some(score) = report.score {
+        page.line(&format!("SSIM: {:.4}", score), 9.0, false);
+    }
+}
+
+/// Draws the reference, captured and marked images side by side.
+fn draw_images(page: &mut Page, images: &Images) -> Result<()> {
+    let mut shown = vec![("Capture", images.capture), ("Defects", images.annotated)];
+    if let Some(reference) = images.reference {
+        shown.insert(0, ("Reference", reference));
+    }
+
+    let gap = 10.0;
+    let width = (PAGE_WIDTH - 2.0 * MARGIN - 2.0 * gap) / 3.0;
+    let height = width * 4.0 / 3.0;
+
+    page.heading("Images");
+    page.reserve(height + 16.0);
+
+    for (i, (name, src)) in shown.iter().enumerate() {
+        let x = MARGIN + i as f64 * (width + gap);
+        show_text(&page.ctx, x, page.y + 9.0, 9.0, false, name);
+        draw_image(&page.ctx, src, x, page.y + 14.0, width, height)?;
+    }
+
+    page.y += height + 16.0;
+    return Ok(());
+}
+
+/// Writes the settings the inspection was run with.
+fn draw_settings(page: &mut Page, report: &Report) {
+    let settings = &report.settings;
+    let filter = &settings.filter;
+    let roi = &settings.roi;
+
+    page.heading("Settings");
+    page.line(&format!("Diff mode: {}", settings.mode.id()), 9.0, false);
+    page.line(&format!("Significance: {}", settings.significance), 9.0, false);
+    page.line(&format!("ΔE threshold: {}", settings.delta_e), 9.0, false);
+    page.line(&format!("Auto align: {}", settings.align), 9.0, false);
+    page.line(&format!("Noise filter: {} ({}, radius {})", filter.op.id(), filter.kernel.id(), filter.radius), 9.0, false);
+    page.line(&format!("Min defect area: {} px", filter.min_area), 9.0, false);
+    page.line(&format!(
+        "Region of interest: {} include, {} exclude shapes, mask {}",
+        roi.include.len(), roi.exclude.len(), roi.mask.as_deref().unwrap_or("none"),
+    ), 9.0, false);
+
+    if report.zones.is_empty() == false {
+        page.heading("Zones");
+    }
+
+    for zone in report.zones.iter() {
+        let mark = if zone.passed { "pass" } else { "fail" };
+        page.line(&format!(
+            "Zone {}: {} defects ({} allowed), {} of {} px  {}",
+            zone.name, zone.defects, zone.max_defects, zone.counter, zone.area, mark,
+        ), 9.0, false);
+    }
+}
+
+/// Writes the defect table header.
+fn draw_table_header(page: &mut Page) {
+    page.reserve(ROW_HEIGHT + 16.0);
+    page.y += 12.0;
+
+    for (title, x) in COLUMNS.iter() {
+        show_text(&page.ctx, MARGIN + x, page.y, 9.0, true, title);
+    }
+
+    page.y += 6.0;
+}
+
+/// Writes the defect table, one row with a crop per defect.
+fn draw_defects(page: &mut Page, report: &Report, images: &Images) -> Result<()> {
+    page.heading("Defects");
+
+    if report.defects.is_empty() {
+        page.line("No defects found.", 9.0, false);
+        return Ok(());
+    }
+
+    draw_table_header(page);
+
+    for defect in report.defects.iter() {
+        if page.y + ROW_HEIGHT > PAGE_HEIGHT - MARGIN {
+            page.next();
+            draw_table_header(page);
+        }
+
+        let crop = report::crop(images.annotated, defect);
+        draw_image(&page.ctx, &crop, MARGIN, page.y + 2.0, 40.0, 40.0)?;
+
+        let cells = [
+            defect.id.to_string(),
+            defect.class.name().to_string(),
+            defect.x.to_string(),
+            defect.y.to_string(),
+            format!("{} x {}", defect.width, defect.height),
+            defect.area.to_string(),
+            format!("{:.1} / {}", defect.mean_contrast, defect.max_contrast),
+            defect.zone.clone().unwrap_or(String::from("-")),
+        ];
+
+        let baseline = page.y + ROW_HEIGHT / 2.0 + 3.0;
+        for (cell, (_, x)) in cells.iter().zip(COLUMNS.iter().skip(1)) {
+            show_text(&page.ctx, MARGIN + x, baseline, 9.0, false, cell);
+        }
+
+        page.y += ROW_HEIGHT;
+    }
+
+    return Ok(());
+}
+
+/// Renders an inspection report into a PDF file.
+pub fn render(report: &Report, images: &Images, path: &str) -> Result<()> {
+    let surface = PdfSurface::new(PAGE_WIDTH, PAGE_HEIGHT, path).map_err(|err| Error::Report(err.to_string()))?;
+
+    let mut page = Page{
+        ctx: Context::new(&surface),
+        y: MARGIN,
+        number: 1,
+    };
+
+    draw_title(&mut page, report);
+    draw_inputs(&mut page, report);
+    draw_summary(&mut page, report);
+    draw_images(&mut page, images)?;
+    draw_settings(&mut page, report);
+    draw_defects(&mut page, report, images)?;
+
+    footer(&page.ctx, page.number);
+    page.ctx.show_page();
+    surface.finish();
+
+    return Ok(());
+}
@@ -0,0 +1,248 @@
+//! Recipe module for Glassvis.
+//!
+//! A recipe is a named set of inspection settings and output options for one
+//! product, stored as a TOML or JSON file (chosen by file extension):
+//!
+//! ```toml
+//! name = "Panel 24in"
+//! description = "Front glass of the 24 inch panel"
+//!
+//! [settings]
+//! mode = "de2000"
+//! significance = 10
+//! delta_e = 2.3
+//! ssim_radius = 3
+//! align = true
+//!
+//! [settings.filter]
+//! op = "open"
+//! kernel = "square"
+//! radius = 1
+//! min_area = 4
+//!
+//! [[settings.roi.include]]
+//! shape = "rect"
+//! x = 20
+//! y = 20
+//! width = 560
+//! height = 760
+//!
+//! [[settings.roi.exclude]]
+//! shape = "polygon"
+//! points = [[40, 30], [120, 30], [80, 90]]
+//!
+//! [output]
+//! bbox = true
+//! marker = [255, 0, 0]
+//!
+//! [limits]
+//! max_rate = 0.05
+//!
+//! [limits.max_count]
+//! scratch = 0
+//! ```
+//!
+//! Missing fields fall back to their defaults.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::aux;
+use crate::error::{Error, Result};
+use crate::verdict::Limits;
+use crate::Settings;
+
+/// Represents the output options of an inspection.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Output {
+    /// Draws a bounding box around each defect.
+    pub bbox: bool,
+    /// Color of marked defect pixels.
+    pub marker: [u8; 3],
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        return Output{
+            bbox: true,
+            marker: [255, 0, 0],
+        };
+    }
+}
+
+/// Represents a named inspection recipe.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Recipe {
+    pub name: String,
+    pub description: String,
+    pub settings: Settings,
+    pub output: Output,
+    /// Acceptance limits the verdict is judged by.
+    pub limits: Limits,
+}
+
+impl Default for Recipe {
+    fn default() -> Recipe {
+        return Recipe{
+            name: String::from("default"),
+            description: String::new(),
+            settings: Settings::default(),
+            output: Output::default(),
+            limits: Limits::default(),
+        };
+    }
+}
+
+/// Represents the supported recipe file formats.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    Toml,
+    Json,
+}
+
+/// Detects the recipe file format from a file extension.
+fn format(path: &Path) -> Result<Format> {
+    let ext = path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+
+    let format = match ext.as_deref() {
+        Some("toml") => Format::Toml,
+        Some("json") => Format::Json,
+        _ => return Err(Error::Recipe(format!("unsupported file type '{}'", path.display()))),
+    };
+
+    return Ok(format);
+}
+
+impl Recipe {
+    /// Loads a recipe from a TOML or JSON file.
+    pub fn load(path: &str) -> Result<Recipe> {
+        let path = Path::new(path);
+        let text = fs::read_to_string(path)?;
+
+        let recipe: Recipe = match format(path)? {
+            Format::Toml => toml::from_str(&text).map_err(|err| Error::Recipe(err.to_string()))?,
+            Format::Json => serde_json::from_str(&text).map_err(|err| Error::Recipe(err.to_string()))?,
+        };
+
+        recipe.settings.validate()?;
+        recipe.limits.validate()?;
+
+        return Ok(recipe);
+    }
+
+    /// Saves a recipe to a TOML or JSON file.
+    pub fn save(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+
+        let text = match format(path)? {
+            Format::Toml => toml::to_string(self).map_err(|err| Error::Recipe(err.to_string()))?,
+            Format::Json => serde_json::to_string_pretty(self).map_err(|err| Error::Recipe(err.to_string()))?,
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, text)?;
+
+        return Ok(());
+    }
+}
+
+/// Returns "data/recipes" directory path (as path buffer).
+pub fn get_path() -> PathBuf {
+    let mut path = aux::get_path();
+    path.push("recipes");
+    return path;
+}
+
+/// Lists the recipe files of a directory, sorted by file name.
+pub fn list(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return files,
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_file() && format(&path).is_ok() {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    return files;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a TOML recipe into the temp directory and loads it.
+    fn load_toml(name: &str, text: &str) -> Result<Recipe> {
+        let path = std::env::temp_dir().join(format!("glassvis-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let recipe = Recipe::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        return recipe;
+    }
+
+    #[test]
+    fn loads_valid_significance() {
+        let recipe = load_toml("valid", "[settings]\nsignificance = 100\n").unwrap();
+        assert_eq!(recipe.settings.significance, 100);
+    }
+
+    #[test]
+    fn rejects_zero_significance() {
+        let recipe = load_toml("zero", "[settings]\nsignificance = 0\n");
+        assert!(matches!(recipe, Err(Error::Recipe(_))));
+    }
+
+    #[test]
+    fn rejects_significance_above_range() {
+        let recipe = load_toml("above", "[settings]\nsignificance = 101\n");
+        assert!(matches!(recipe, Err(Error::Recipe(_))));
+    }
+
+    #[test]
+    fn rejects_non_positive_delta_e() {
+        let recipe = load_toml("delta", "[settings]\ndelta_e = 0.0\n");
+        assert!(matches!(recipe, Err(Error::Recipe(_))));
+    }
+
+    #[test]
+    fn rejects_zero_zone_significance() {
+        let recipe = load_toml("zone", "[[settings.zones]]\nname = \"A\"\nsignificance = 0\n");
+        assert!(matches!(recipe, Err(Error::Recipe(_))));
+    }
+
+    #[test]
+    fn loads_valid_limits() {
+        let recipe = load_toml("limits", "[limits]\nmax_rate = 0.05\nreview = 0.8\n\n[limits.max_count]\nscratch = 0\n").unwrap();
+        assert_eq!(recipe.limits.max_rate, Some(0.05));
+        assert_eq!(recipe.limits.review, 0.8);
+        assert_eq!(load_toml("no-limits", "").unwrap().limits, Limits::default());
+    }
+
+    #[test]
+    fn rejects_out_of_range_limits() {
+        for (name, text) in [
+            ("rate", "[limits]\nmax_rate = -1.0\n"),
+            ("review-low", "[limits]\nreview = -0.1\n"),
+            ("review-high", "[limits]\nreview = 1.5\n"),
+            ("area", "[limits]\nmax_area = -5\n"),
+            ("class", "[limits.max_count]\ncrack = 1\n"),
+        ].iter() {
+            assert!(matches!(load_toml(name, text), Err(Error::Recipe(_))), "{}", name);
+        }
+    }
+}
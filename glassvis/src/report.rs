@@ -0,0 +1,472 @@
+//! Report module for Glassvis.
+//!
+//! A report is the machine-readable result of one inspection: the inputs with
+//! their SHA-256 hashes, the settings used, the image dimensions, the defect
+//! list and the verdict with its findings, stamped with the inspection time. It is written as JSON
+//! for downstream tooling (e.g. MES), as a CSV defect list for spreadsheets,
+//! rendered as a self-contained HTML page, or rendered as a printable PDF
+//! certificate (with the "pdf" feature).
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+use chrono::Local;
+use image::{DynamicImage, GenericImageView};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::classify::{DefectClass, Features};
+use crate::error::{Error, Result};
+use crate::html;
+use crate::imgfx::Defect;
+#[cfg(feature = "pdf")]
+use crate::pdf;
+use crate::verdict::{Finding, Judgement, Verdict};
+use crate::zone::Grade;
+use crate::{Inspection, Settings};
+
+/// Margin around a defect box in its crop, in pixels.
+const CROP_MARGIN: u32 = 8;
+
+/// Header row of the CSV defect list.
+const CSV_HEADER: &str = "panel,defect,x,y,width,height,area,mean_contrast,max_contrast,class,zone\n";
+
+/// Represents an input image file (or an in-memory frame, named by its source).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Input {
+    pub path: String,
+    /// SHA-256 hash of the file contents, or of the raw pixel data of a frame (hex).
+    pub sha256: String,
+}
+
+impl Input {
+    /// Describes an input file along with its hash.
+    pub fn from_file(path: &str) -> Result<Input> {
+        let input = Input{
+            path: path.to_string(),
+            sha256: hash_file(path)?,
+        };
+
+        return Ok(input);
+    }
+
+    /// Describes an in-memory frame (e.g. a live camera frame) along with the hash of its pixel data.
+    pub fn from_frame(name: &str, frame: &DynamicImage) -> Input {
+        return Input{
+            path: name.to_string(),
+            sha256: hash_bytes(frame.as_bytes()),
+        };
+    }
+}
+
+/// Represents a defect record of a report.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DefectRecord {
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub area: u32,
+    pub centroid: [f32; 2],
+    pub mean_contrast: f32,
+    pub max_contrast: u8,
+    pub class: DefectClass,
+    pub features: Features,
+    pub zone: Option<String>,
+}
+
+impl DefectRecord {
+    /// Creates a defect record from a segmented defect.
+    pub fn from_defect(defect: &Defect) -> DefectRecord {
+        return DefectRecord{
+            id: defect.id,
+            x: defect.rect.left(),
+            y: defect.rect.top(),
+            width: defect.rect.width(),
+            height: defect.rect.height(),
+            area: defect.area,
+            centroid: [defect.centroid.0, defect.centroid.1],
+            mean_contrast: defect.mean_contrast,
+            max_contrast: defect.max_contrast,
+            class: defect.class,
+            features: defect.features,
+            zone: defect.zone.clone(),
+        };
+    }
+}
+
+/// Represents a zone record of a report.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZoneRecord {
+    pub name: String,
+    pub area: u32,
+    pub counter: u32,
+    pub defects: u32,
+    pub max_defects: u32,
+    pub passed: bool,
+}
+
+impl ZoneRecord {
+    /// Creates a zone record from a zone grade.
+    pub fn from_grade(grade: &Grade) -> ZoneRecord {
+        return ZoneRecord{
+            name: grade.name.clone(),
+            area: grade.area,
+            counter: grade.counter,
+            defects: grade.defects,
+            max_defects: grade.max_defects,
+            passed: grade.passed(),
+        };
+    }
+}
+
+/// Represents the machine-readable result of an inspection.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    /// Local inspection time (RFC 3339).
+    pub timestamp: String,
+    /// Reference image (none for hotspot detection).
+    pub reference: Option<Input>,
+    pub capture: Input,
+    pub settings: Settings,
+    pub width: u32,
+    pub height: u32,
+    pub defect_count: u32,
+    /// Number of defect pixels.
+    pub counter: u32,
+    /// Percentage of defect pixels among the inspected pixels.
+    pub defect_rate: f32,
+    /// Global SSIM index (SSIM mode only).
+    pub score: Option<f32>,
+    pub defects: Vec<DefectRecord>,
+    pub zones: Vec<ZoneRecord>,
+    pub verdict: Verdict,
+    /// Rules that were broken or nearly broken.
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+}
+
+impl Report {
+    /// Creates a report of an inspection of given input files, stamped with the current time.
+    pub fn new(
+        inspection: &Inspection,
+        settings: &Settings,
+        reference: Option<&str>,
+        capture: &str,
+        judgement: &Judgement,
+    ) -> Result<Report> {
+        let reference = match reference {
+            Some(path) => Some(Input::from_file(path)?),
+            None => None,
+        };
+
+        let report = Report::from_inputs(inspection, settings, reference, Input::from_file(capture)?, judgement);
+        return Ok(report);
+    }
+
+    /// Creates a report of an inspection of given inputs, stamped with the current time.
+    pub fn from_inputs(
+        inspection: &Inspection,
+        settings: &Settings,
+        reference: Option<Input>,
+        capture: Input,
+        judgement: &Judgement,
+    ) -> Report {
+        let (width, height) = inspection.dimensions();
+
+        let report = Report{
+            timestamp: Local::now().to_rfc3339(),
+            reference: reference,
+            capture: capture,
+            settings: settings.clone(),
+            width: width,
+            height: height,
+            defect_count: inspection.defects.len() as u32,
+            counter: inspection.counter,
+            defect_rate: inspection.defect_rate(),
+            score: inspection.score,
+            defects: inspection.defects.iter().map(DefectRecord::from_defect).collect(),
+            zones: inspection.grades.iter().map(ZoneRecord::from_grade).collect(),
+            verdict: judgement.verdict,
+            findings: judgement.findings.clone(),
+        };
+
+        return report;
+    }
+
+    /// Serializes the report as pretty-printed JSON.
+    pub fn to_json(&self) -> Result<String> {
+        let text = serde_json::to_string_pretty(self).map_err(|err| Error::Report(err.to_string()))?;
+        return Ok(text);
+    }
+
+    /// Saves the report to a JSON file.
+    pub fn save_json(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+        let text = self.to_json()?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, text)?;
+
+        return Ok(());
+    }
+
+    /// Returns the panel id of the report (the file stem of the captured image).
+    pub fn panel_id(&self) -> String {
+        let id = match Path::new(&self.capture.path).file_stem() {
+            Some(stem) => stem.to_string_lossy().to_string(),
+            None => self.capture.path.clone(),
+        };
+
+        return id;
+    }
+
+    /// Formats the defect list as CSV rows, one per defect, without a header.
+    pub fn to_csv(&self) -> String {
+        let panel = csv_field(&self.panel_id());
+        let mut text = String::new();
+
+        for defect in self.defects.iter() {
+            text.push_str(&format!("{},{},{},{},{},{},{},{:.2},{},{},{}\n",
+                panel, defect.id, defect.x, defect.y, defect.width, defect.height, defect.area,
+                defect.mean_contrast, defect.max_contrast, csv_field(defect.class.name()),
+                csv_field(defect.zone.as_deref().unwrap_or(""))));
+        }
+
+        return text;
+    }
+
+    /// Saves the defect list to a CSV file.
+    pub fn save_csv(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, format!("{}{}", CSV_HEADER, self.to_csv()))?;
+
+        return Ok(());
+    }
+
+    /// Appends the defect list to a cumulative CSV file, writing the header if the file is new.
+    pub fn append_csv(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        if file.metadata()?.len() == 0 {
+            file.write_all(CSV_HEADER.as_bytes())?;
+        }
+
+        file.write_all(self.to_csv().as_bytes())?;
+
+        return Ok(());
+    }
+
+    /// Renders the report along with its images into a self-contained HTML file.
+    pub fn save_html(&self, images: &Images, path: &str) -> Result<()> {
+        let path = Path::new(path);
+        let text = html::render(self, images)?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(path, text)?;
+
+        return Ok(());
+    }
+
+    /// Renders the report along with its images into a PDF file.
+    #[cfg(feature = "pdf")]
+    pub fn save_pdf(&self, images: &Images, path: &str) -> Result<()> {
+        return pdf::render(self, images, path);
+    }
+
+    /// Fails, since PDF reports need the "pdf" feature.
+    #[cfg(not(feature = "pdf"))]
+    pub fn save_pdf(&self, _images: &Images, _path: &str) -> Result<()> {
+        return Err(Error::Report(String::from("PDF support is disabled (build with the \"pdf\" feature)")));
+    }
+}
+
+/// Represents the images shown in a printable or HTML report.
+pub struct Images<'a> {
+    /// Reference image (none for hotspot detection).
+    pub reference: Option<&'a DynamicImage>,
+    /// Inspected image (the captured image after alignment, if enabled).
+    pub capture: &'a DynamicImage,
+    /// Inspected image with marked defects.
+    pub annotated: &'a DynamicImage,
+}
+
+/// Crops a defect from an image, with a margin around its box.
+pub fn crop(src: &DynamicImage, defect: &DefectRecord) -> DynamicImage {
+    let (width, height) = src.dimensions();
+    let left = (defect.x.max(0) as u32).min(width.saturating_sub(1));
+    let top = (defect.y.max(0) as u32).min(height.saturating_sub(1));
+
+    let x0 = left.saturating_sub(CROP_MARGIN);
+    let y0 = top.saturating_sub(CROP_MARGIN);
+    let x1 = (left + defect.width + CROP_MARGIN).min(width);
+    let y1 = (top + defect.height + CROP_MARGIN).min(height);
+
+    let dst = src.crop_imm(x0, y0, (x1 - x0).max(1), (y1 - y0).max(1));
+    return dst;
+}
+
+/// Quotes a CSV field if it contains separators, quotes or line breaks.
+pub(crate) fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        return format!("\"{}\"", text.replace('"', "\"\""));
+    }
+
+    return text.to_string();
+}
+
+/// Computes the SHA-256 hash (hex) of a byte buffer.
+fn hash_bytes(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let hex: Vec<String> = digest.iter().map(|b| format!("{:02x}", b)).collect();
+
+    return hex.concat();
+}
+
+/// Computes the SHA-256 hash (hex) of a file.
+pub fn hash_file(path: &str) -> Result<String> {
+    let bytes = fs::read(path)?;
+    return Ok(hash_bytes(&bytes));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GrayImage;
+    use imageproc::rect::Rect;
+
+    /// Creates a report of a capture with given path holding one defect in given zone.
+    fn report(capture: &str, zone: &str) -> Report {
+        let defect = Defect{
+            id: 1,
+            rect: Rect::at(3, 4).of_size(5, 6),
+            area: 12,
+            centroid: (5.0, 7.0),
+            mean_contrast: 42.345,
+            max_contrast: 90,
+            zone: Some(zone.to_string()),
+            features: Features::default(),
+            class: DefectClass::EdgeChip,
+        };
+
+        let inspection = Inspection{
+            image: DynamicImage::new_luma8(20, 20),
+            mask: GrayImage::new(20, 20),
+            defects: vec![defect],
+            counter: 12,
+            area: 400,
+            grades: Vec::new(),
+            score: None,
+            alignment: None,
+            alignment_error: None,
+        };
+
+        let judgement = Judgement{ verdict: Verdict::Pass, findings: Vec::new() };
+        let capture = Input{ path: capture.to_string(), sha256: String::new() };
+
+        return Report::from_inputs(&inspection, &Settings::default(), None, capture, &judgement);
+    }
+
+    /// Returns a fresh path with given extension in the temp directory.
+    fn temp_path(name: &str, ext: &str) -> String {
+        let path = std::env::temp_dir().join(format!("glassvis-{}-{}.{}", name, std::process::id(), ext));
+        let _ = fs::remove_file(&path);
+        return path.to_string_lossy().to_string();
+    }
+
+    #[test]
+    fn quotes_csv_fields() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn formats_csv_rows() {
+        let text = report("/data/panel,7.png", "band \"A\"").to_csv();
+        assert_eq!(text, "\"panel,7\",1,3,4,5,6,12,42.35,90,edge chip,\"band \"\"A\"\"\"\n");
+    }
+
+    #[test]
+    fn appends_header_once() {
+        let path = temp_path("append", "csv");
+        report("/data/p1.png", "vision").append_csv(&path).unwrap();
+        report("/data/p2.png", "band").append_csv(&path).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(format!("{}\n", lines[0]), CSV_HEADER);
+        assert!(lines[1].starts_with("p1,1,"));
+        assert!(lines[2].starts_with("p2,1,"));
+    }
+
+    #[test]
+    fn save_overwrites() {
+        let path = temp_path("save", "csv");
+        report("/data/p1.png", "vision").save_csv(&path).unwrap();
+        report("/data/p2.png", "band").save_csv(&path).unwrap();
+
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(text, format!("{}{}", CSV_HEADER, report("/data/p2.png", "band").to_csv()));
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let mut report = report("/data/p1.png", "vision");
+        report.reference = Some(Input{ path: String::from("/data/ref.png"), sha256: String::from("ab12") });
+        report.capture.sha256 = String::from("cd34");
+
+        let path = temp_path("report", "json");
+        report.save_json(&path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let loaded: Report = serde_json::from_str(&text).unwrap();
+        assert_eq!(loaded, report);
+    }
+
+    #[test]
+    fn json_holds_required_fields() {
+        let mut report = report("/data/p1.png", "vision");
+        report.reference = Some(Input{ path: String::from("/data/ref.png"), sha256: String::from("ab12") });
+        report.capture.sha256 = String::from("cd34");
+
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["reference"]["sha256"], "ab12");
+        assert_eq!(json["capture"]["path"], "/data/p1.png");
+        assert_eq!(json["capture"]["sha256"], "cd34");
+        assert_eq!(json["settings"]["significance"], 10);
+        assert_eq!(json["settings"]["mode"], "luma");
+        assert_eq!(json["defect_count"], 1);
+        assert_eq!(json["defects"][0]["class"], "edge-chip");
+        assert_eq!(json["defects"][0]["zone"], "vision");
+        assert_eq!(json["defects"][0]["area"], 12);
+        assert_eq!(json["verdict"], "PASS");
+    }
+}
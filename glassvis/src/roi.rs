@@ -0,0 +1,341 @@
+//! Region-of-interest module for Glassvis.
+//!
+//! A region of interest limits an inspection to the pixels that matter. It is
+//! built from an optional binary mask image (white pixels are inspected),
+//! inclusion shapes (only their union is inspected) and exclusion shapes
+//! (logos, frame clamps, suction-cup marks), which always win. Shapes are given in
+//! the pixel coordinates of the inspected images and are scaled along with them.
+
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::drawing::{draw_hollow_rect_mut, draw_line_segment_mut};
+use imageproc::rect::Rect;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{check_dimensions, Result};
+use crate::imgfx;
+
+/// Represents a shape drawn in image coordinates.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "lowercase")]
+pub enum Shape {
+    Rect { x: i32, y: i32, width: u32, height: u32 },
+    Polygon { points: Vec<[i32; 2]> },
+}
+
+impl Shape {
+    /// Creates a rectangle spanned by two corner points.
+    pub fn rect_between(a: [i32; 2], b: [i32; 2]) -> Shape {
+        let shape = Shape::Rect{
+            x: a[0].min(b[0]),
+            y: a[1].min(b[1]),
+            width: (a[0] - b[0]).unsigned_abs() + 1,
+            height: (a[1] - b[1]).unsigned_abs() + 1,
+        };
+
+        return shape;
+    }
+
+    /// Returns the shape scaled by the given factors, e.g. onto a resized image.
+    pub fn scaled(&self, sx: f64, sy: f64) -> Shape {
+        match self {
+            Shape::Rect { x, y, width, height } => {
+                let left = (*x as f64 * sx).round() as i32;
+                let top = (*y as f64 * sy).round() as i32;
+                let right = ((*x as i64 + *width as i64) as f64 * sx).round() as i32;
+                let bottom = ((*y as i64 + *height as i64) as f64 * sy).round() as i32;
+
+                return Shape::Rect{
+                    x: left,
+                    y: top,
+                    width: (right - left).max(1) as u32,
+                    height: (bottom - top).max(1) as u32,
+                };
+            }
+            Shape::Polygon { points } => {
+                return Shape::Polygon{ points: scale_points(points, sx, sy) };
+            }
+        }
+    }
+
+    /// Returns the polygon vertices without a repeated closing vertex.
+    fn vertices(points: &Vec<[i32; 2]>) -> Vec<[i32; 2]> {
+        let mut vertices = points.clone();
+        vertices.dedup();
+
+        while vertices.len() > 1 && vertices[0] == vertices[vertices.len() - 1] {
+            vertices.pop();
+        }
+
+        return vertices;
+    }
+
+    /// Fills the shape into a mask with the given value.
+    pub(crate) fn fill(&self, mask: &mut GrayImage, value: u8) {
+        let (width, height) = mask.dimensions();
+
+        match self {
+            Shape::Rect { x, y, width: w, height: h } => {
+                let x0 = (*x).max(0) as u32;
+                let y0 = (*y).max(0) as u32;
+                let x1 = (*x as i64 + *w as i64).min(width as i64).max(0) as u32;
+                let y1 = (*y as i64 + *h as i64).min(height as i64).max(0) as u32;
+
+                for py in y0..y1 {
+                    for px in x0..x1 {
+                        mask.put_pixel(px, py, Luma([value]));
+                    }
+                }
+            }
+            Shape::Polygon { points } => {
+                let vertices = Shape::vertices(points);
+                if vertices.len() >= 3 {
+                    fill_polygon(mask, &vertices, value);
+                }
+            }
+        }
+    }
+
+    /// Draws the outline of the shape.
+    pub(crate) fn outline(&self, dst: &mut RgbaImage, color: Rgba<u8>) {
+        match self {
+            Shape::Rect { x, y, width, height } => {
+                let rect = Rect::at(*x, *y).of_size((*width).max(1), (*height).max(1));
+                draw_hollow_rect_mut(dst, rect, color);
+            }
+            Shape::Polygon { points } => {
+                let vertices = Shape::vertices(points);
+                draw_path(dst, &vertices, true, color);
+            }
+        }
+    }
+}
+
+/// Represents the inclusion and exclusion masks of an inspection.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Roi {
+    /// Binary mask image whose white pixels are inspected.
+    pub mask: Option<String>,
+    /// Shapes whose union is inspected (the whole image if empty).
+    pub include: Vec<Shape>,
+    /// Shapes that are never inspected.
+    pub exclude: Vec<Shape>,
+}
+
+impl Roi {
+    /// Checks if the whole image is inspected.
+    pub fn is_empty(&self) -> bool {
+        return self.mask.is_none() && self.include.is_empty() && self.exclude.is_empty();
+    }
+
+    /// Returns the region of interest with its shapes scaled by the given factors.
+    /// The mask file is kept, it must be made for the scaled image size.
+    pub fn scaled(&self, sx: f64, sy: f64) -> Roi {
+        return Roi{
+            mask: self.mask.clone(),
+            include: self.include.iter().map(|shape| shape.scaled(sx, sy)).collect(),
+            exclude: self.exclude.iter().map(|shape| shape.scaled(sx, sy)).collect(),
+        };
+    }
+
+    /// Renders the region of interest into a mask whose inspected pixels are white.
+    pub fn render(&self, width: u32, height: u32) -> Result<GrayImage> {
+        let mut dst = GrayImage::from_pixel(width, height, Luma([255u8]));
+
+        if let Some(mask_file) = &self.mask {
+            let mask = imgfx::open_luma(mask_file)?;
+            check_dimensions((width, height), mask.dimensions())?;
+
+            for (x, y, pix) in mask.enumerate_pixels() {
+                if pix[0] < 128 {
+                    dst.put_pixel(x, y, Luma([0u8]));
+                }
+            }
+        }
+
+        if self.include.is_empty() == false {
+            let mut union = GrayImage::new(width, height);
+            for shape in self.include.iter() {
+                shape.fill(&mut union, 255);
+            }
+
+            for (x, y, pix) in union.enumerate_pixels() {
+                if pix[0] == 0 {
+                    dst.put_pixel(x, y, Luma([0u8]));
+                }
+            }
+        }
+
+        for shape in self.exclude.iter() {
+            shape.fill(&mut dst, 0);
+        }
+
+        return Ok(dst);
+    }
+}
+
+/// Scales points by the given factors, rounding them to the nearest pixel.
+pub fn scale_points(points: &[[i32; 2]], sx: f64, sy: f64) -> Vec<[i32; 2]> {
+    return points.iter()
+        .map(|p| [(p[0] as f64 * sx).round() as i32, (p[1] as f64 * sy).round() as i32])
+        .collect();
+}
+
+/// Fills a (possibly concave) polygon into a mask by even-odd scanlines through the
+/// pixel centers, then draws its edges so that the outline pixels are filled too.
+fn fill_polygon(mask: &mut GrayImage, vertices: &[[i32; 2]], value: u8) {
+    let (width, height) = mask.dimensions();
+    let top = vertices.iter().map(|p| p[1]).min().unwrap_or(0).max(0);
+    let bottom = vertices.iter().map(|p| p[1]).max().unwrap_or(-1).min(height as i32 - 1);
+    let edges: Vec<([i32; 2], [i32; 2])> = (0..vertices.len())
+        .map(|i| (vertices[i], vertices[(i + 1) % vertices.len()]))
+        .collect();
+
+    for y in top..=bottom {
+        // crossings of the edges with the scanline, each edge counted half-open
+        let mut xs: Vec<f32> = edges.iter()
+            .filter(|(a, b)| (a[1] <= y && y < b[1]) || (b[1] <= y && y < a[1]))
+            .map(|(a, b)| a[0] as f32 + (y - a[1]) as f32 * (b[0] - a[0]) as f32 / (b[1] - a[1]) as f32)
+            .collect();
+        xs.sort_by(|a, b| a.total_cmp(b));
+
+        for span in xs.chunks(2).filter(|span| span.len() == 2) {
+            let x0 = span[0].ceil().max(0.0) as i64;
+            let x1 = span[1].floor().min(width as f32 - 1.0) as i64;
+
+            for x in x0..=x1 {
+                mask.put_pixel(x as u32, y as u32, Luma([value]));
+            }
+        }
+    }
+
+    draw_path(mask, vertices, true, Luma([value]));
+}
+
+/// Draws connected line segments through the given points.
+fn draw_path<C>(dst: &mut C, points: &[[i32; 2]], closed: bool, color: C::Pixel)
+where
+    C: imageproc::drawing::Canvas,
+    C::Pixel: 'static,
+{
+    let mut segments: Vec<([i32; 2], [i32; 2])> = points.windows(2).map(|w| (w[0], w[1])).collect();
+
+    if closed && points.len() > 2 {
+        segments.push((points[points.len() - 1], points[0]));
+    }
+
+    for (a, b) in segments {
+        draw_line_segment_mut(dst, (a[0] as f32, a[1] as f32), (b[0] as f32, b[1] as f32), color);
+    }
+}
+
+/// Shades the pixels outside the region of interest and outlines its shapes,
+/// along with the vertices of a shape still being drawn.
+pub fn overlay(src: &DynamicImage, roi: &Roi, draft: &[[i32; 2]]) -> Result<DynamicImage> {
+    let (width, height) = src.dimensions();
+    let mask = roi.render(width, height)?;
+    let mut dst = src.to_rgba8();
+
+    for (x, y, pix) in dst.enumerate_pixels_mut() {
+        if mask.get_pixel(x, y)[0] == 0 {
+            pix[0] /= 3;
+            pix[1] /= 3;
+            pix[2] /= 3;
+        }
+    }
+
+    for shape in roi.include.iter() {
+        shape.outline(&mut dst, Rgba([0, 255, 0, 255]));
+    }
+
+    for shape in roi.exclude.iter() {
+        shape.outline(&mut dst, Rgba([255, 160, 0, 255]));
+    }
+
+    draw_path(&mut dst, draft, false, Rgba([255, 255, 0, 255]));
+
+    return Ok(DynamicImage::ImageRgba8(dst));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the number of inspected pixels of a rendered region.
+    fn inspected(mask: &GrayImage) -> usize {
+        return mask.pixels().filter(|p| p[0] > 0).count();
+    }
+
+    #[test]
+    fn empty_roi_inspects_everything() {
+        let mask = Roi::default().render(20, 10).unwrap();
+        assert_eq!(inspected(&mask), 200);
+    }
+
+    #[test]
+    fn includes_union_of_shapes() {
+        let roi = Roi{
+            include: vec![
+                Shape::Rect{ x: 0, y: 0, width: 5, height: 5 },
+                Shape::Rect{ x: 3, y: 3, width: 5, height: 5 },
+            ],
+            ..Roi::default()
+        };
+        let mask = roi.render(20, 20).unwrap();
+
+        // two 5x5 squares overlapping in a 2x2 square
+        assert_eq!(inspected(&mask), 46);
+        assert_eq!(mask.get_pixel(4, 4)[0], 255);
+        assert_eq!(mask.get_pixel(10, 10)[0], 0);
+    }
+
+    #[test]
+    fn exclusion_wins_over_inclusion() {
+        let roi = Roi{
+            include: vec![Shape::Rect{ x: 0, y: 0, width: 10, height: 10 }],
+            exclude: vec![Shape::Rect{ x: 2, y: 2, width: 3, height: 3 }],
+            ..Roi::default()
+        };
+        let mask = roi.render(10, 10).unwrap();
+
+        assert_eq!(inspected(&mask), 91);
+        assert_eq!(mask.get_pixel(3, 3)[0], 0);
+    }
+
+    #[test]
+    fn fills_polygons_and_clips_shapes() {
+        let roi = Roi{
+            include: vec![Shape::Polygon{ points: vec![[0, 0], [9, 0], [9, 9], [0, 9], [0, 0]] }],
+            exclude: vec![Shape::Rect{ x: -5, y: -5, width: 7, height: 7 }],
+            ..Roi::default()
+        };
+        let mask = roi.render(10, 10).unwrap();
+
+        assert_eq!(inspected(&mask), 96);
+    }
+
+    #[test]
+    fn scales_shapes() {
+        let rect = Shape::Rect{ x: 10, y: 20, width: 30, height: 40 };
+        assert_eq!(rect.scaled(0.5, 0.25), Shape::Rect{ x: 5, y: 5, width: 15, height: 10 });
+
+        let polygon = Shape::Polygon{ points: vec![[0, 0], [10, 0], [10, 10]] };
+        assert_eq!(polygon.scaled(2.0, 3.0), Shape::Polygon{ points: vec![[0, 0], [20, 0], [20, 30]] });
+    }
+
+    #[test]
+    fn fills_concave_polygons() {
+        let roi = Roi{
+            include: vec![Shape::Polygon{ points: vec![[0, 0], [9, 0], [9, 4], [4, 4], [4, 9], [0, 9]] }],
+            ..Roi::default()
+        };
+        let mask = roi.render(12, 12).unwrap();
+
+        // L shape of a 10x5 bar and a 5x5 leg, the notch stays unfilled
+        assert_eq!(inspected(&mask), 75);
+        assert_eq!(mask.get_pixel(2, 7)[0], 255);
+        assert_eq!(mask.get_pixel(7, 2)[0], 255);
+        assert_eq!(mask.get_pixel(7, 7)[0], 0);
+        assert_eq!(mask.get_pixel(5, 5)[0], 0);
+    }
+}
@@ -0,0 +1,406 @@
+//! Frame source module for Glassvis.
+//!
+//! A frame source delivers captured images one after another: a V4L2 camera
+//! (with the "v4l2" feature), a single image file, the image files of a
+//! directory in name order, or a synthetic test pattern that needs no hardware.
+//! Sources are opened from a short spec string:
+//!
+//! * `v4l2:/dev/video0`: camera device (MJPG frames, 1280x720)
+//! * `file:data/capt.png`: image file, re-read on every grab
+//! * `dir:data/frames`: image files of a directory, looped
+//! * `synthetic:defects,noise=4,size=600x800`: test pattern, optionally with
+//!   moving defects and sensor noise (all options can be omitted)
+
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use image::{DynamicImage, Rgb, RgbImage};
+
+use crate::aux;
+use crate::error::{Error, Result};
+use crate::imgfx;
+
+/// Grid spacing of the synthetic test pattern in pixels.
+const GRID_SPACING: u32 = 50;
+
+/// Number of empty camera buffers (about one second at 30 fps) skipped before a grab fails.
+#[cfg(feature = "v4l2")]
+const MAX_EMPTY_FRAMES: u32 = 30;
+
+/// Represents a source of captured frames, which can be handed to a worker thread.
+pub trait FrameSource: Send {
+    /// Grabs the next frame.
+    fn grab(&mut self) -> Result<DynamicImage>;
+
+    /// Returns a short description of the source (e.g. its device or path).
+    fn name(&self) -> String;
+}
+
+/// Represents a single image file, re-read on every grab.
+pub struct FileSource {
+    path: String,
+}
+
+impl FileSource {
+    /// Opens an image file as frame source.
+    pub fn open(path: &str) -> Result<FileSource> {
+        if Path::new(path).is_file() == false {
+            return Err(Error::Missing(format!("image file at '{}'", path)));
+        }
+
+        return Ok(FileSource{ path: path.to_string() });
+    }
+}
+
+impl FrameSource for FileSource {
+    fn grab(&mut self) -> Result<DynamicImage> {
+        return imgfx::open(&self.path);
+    }
+
+    fn name(&self) -> String {
+        return self.path.clone();
+    }
+}
+
+/// Represents the image files of a directory, grabbed in file name order.
+pub struct DirSource {
+    dir: String,
+    files: Vec<PathBuf>,
+    next: usize,
+    /// Starts over after the last file instead of failing.
+    pub looping: bool,
+}
+
+impl DirSource {
+    /// Opens the image files of a directory as frame source.
+    pub fn open(dir: &str) -> Result<DirSource> {
+        let files = aux::list_images(Path::new(dir))?;
+
+        if files.is_empty() {
+            return Err(Error::Missing(format!("image files in '{}'", dir)));
+        }
+
+        let source = DirSource{
+            dir: dir.to_string(),
+            files: files,
+            next: 0,
+            looping: true,
+        };
+
+        return Ok(source);
+    }
+}
+
+impl FrameSource for DirSource {
+    fn grab(&mut self) -> Result<DynamicImage> {
+        if self.next >= self.files.len() {
+            if self.looping == false {
+                return Err(Error::Source(format!("no more frames in '{}'", self.dir)));
+            }
+            self.next = 0;
+        }
+
+        let path = self.files[self.next].to_string_lossy().to_string();
+        self.next += 1;
+
+        return imgfx::open(&path);
+    }
+
+    fn name(&self) -> String {
+        return self.dir.clone();
+    }
+}
+
+/// Represents a generated test pattern: a shaded panel with a grid, optionally
+/// with a scratch and a spot moving from frame to frame and with sensor noise.
+pub struct SyntheticSource {
+    pub width: u32,
+    pub height: u32,
+    /// Draws defects onto the pattern.
+    pub defects: bool,
+    /// Maximum noise amplitude in gray levels (0 = noise free).
+    pub noise: u8,
+    frame: u32,
+}
+
+impl SyntheticSource {
+    /// Creates a noise and defect free test pattern source of given size.
+    pub fn new(width: u32, height: u32) -> SyntheticSource {
+        return SyntheticSource{
+            width: width,
+            height: height,
+            defects: false,
+            noise: 0,
+            frame: 0,
+        };
+    }
+
+    /// Returns the pattern value at a pixel.
+    fn pattern(&self, x: u32, y: u32) -> i32 {
+        let shade = 150 + (60 * x / self.width.max(1)) as i32;
+
+        if x % GRID_SPACING == 0 || y % GRID_SPACING == 0 {
+            return shade - 40;
+        }
+        return shade;
+    }
+
+    /// Draws a dark scratch and a dark spot, shifted by the frame number.
+    fn draw_defects(&self, dst: &mut RgbImage) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let shift = (self.frame as i32 * 7) % (width / 2).max(1);
+        let dark = Rgb([40, 40, 40]);
+
+        // diagonal scratch
+        let (x0, y0) = (width / 8 + shift, height / 4);
+        for i in 0..(width / 4) {
+            let (x, y) = (x0 + i, y0 + i / 3);
+            if x >= 0 && x < width && y >= 0 && y < height {
+                dst.put_pixel(x as u32, y as u32, dark);
+            }
+        }
+
+        // round spot
+        let (cx, cy, r) = (width / 3 + shift, height * 2 / 3, 4);
+        for y in (cy - r)..=(cy + r) {
+            for x in (cx - r)..=(cx + r) {
+                let inside = (x - cx) * (x - cx) + (y - cy) * (y - cy) <= r * r;
+                if inside && x >= 0 && x < width && y >= 0 && y < height {
+                    dst.put_pixel(x as u32, y as u32, dark);
+                }
+            }
+        }
+    }
+}
+
+impl FrameSource for SyntheticSource {
+    fn grab(&mut self) -> Result<DynamicImage> {
+        let amplitude = self.noise as i32;
+
+        // linear congruential generator, seeded by the frame number
+        let mut seed = self.frame.wrapping_mul(2654435761).wrapping_add(1);
+        let mut dst = RgbImage::new(self.width, self.height);
+
+        for (x, y, pix) in dst.enumerate_pixels_mut() {
+            let mut value = self.pattern(x, y);
+
+            if amplitude > 0 {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                value += (seed >> 16) as i32 % (2 * amplitude + 1) - amplitude;
+            }
+
+            let value = value.clamp(0, 255) as u8;
+            *pix = Rgb([value, value, value]);
+        }
+
+        if self.defects {
+            self.draw_defects(&mut dst);
+        }
+
+        self.frame += 1;
+
+        return Ok(DynamicImage::ImageRgb8(dst));
+    }
+
+    fn name(&self) -> String {
+        return format!("synthetic {}x{}", self.width, self.height);
+    }
+}
+
+/// Represents a V4L2 camera device delivering MJPG frames.
+#[cfg(feature = "v4l2")]
+pub struct V4l2Source {
+    device: String,
+    camera: rscam::Camera,
+}
+
+#[cfg(feature = "v4l2")]
+impl V4l2Source {
+    /// Opens a camera device and starts streaming at given resolution.
+    pub fn open(device: &str, width: u32, height: u32) -> Result<V4l2Source> {
+        let mut camera = rscam::Camera::new(device)?;
+
+        camera.start(&rscam::Config{
+            // set interval to 30 fps
+            interval: (1, 30),
+            resolution: (width, height),
+            format: b"MJPG",
+            ..Default::default()
+        }).map_err(|err| Error::Source(err.to_string()))?;
+
+        return Ok(V4l2Source{ device: device.to_string(), camera: camera });
+    }
+}
+
+#[cfg(feature = "v4l2")]
+impl FrameSource for V4l2Source {
+    fn grab(&mut self) -> Result<DynamicImage> {
+        // skip empty buffers while the device warms up
+        for _ in 0..MAX_EMPTY_FRAMES {
+            let frame = self.camera.capture()?;
+
+            if frame.is_empty() == false {
+                let dst = image::load_from_memory(&frame[..])?;
+                return Ok(dst);
+            }
+        }
+
+        return Err(Error::Source(format!("camera '{}' delivers empty frames", self.device)));
+    }
+
+    fn name(&self) -> String {
+        return self.device.clone();
+    }
+}
+
+/// Opens the default camera device.
+#[cfg(feature = "v4l2")]
+fn open_camera(device: &str) -> Result<Box<dyn FrameSource>> {
+    return Ok(Box::new(V4l2Source::open(device, 1280, 720)?));
+}
+
+/// Fails, since camera devices need the "v4l2" feature.
+#[cfg(not(feature = "v4l2"))]
+fn open_camera(device: &str) -> Result<Box<dyn FrameSource>> {
+    return Err(Error::Source(format!(
+        "can not open camera '{}', V4L2 support is disabled (build with `--features v4l2`)", device)));
+}
+
+/// Parses the options of a synthetic source spec (e.g. "defects,noise=4,size=600x800").
+fn open_synthetic(options: &str) -> Result<Box<dyn FrameSource>> {
+    let mut source = SyntheticSource::new(600, 800);
+    let invalid = |option: &str| Error::Source(format!("invalid synthetic source option '{}'", option));
+
+    for option in options.split(',').map(|option| option.trim()).filter(|option| option.is_empty() == false) {
+        let mut parts = option.splitn(2, '=');
+        let key = parts.next().unwrap_or_default();
+        let value = parts.next().unwrap_or_default();
+
+        match key {
+            "defects" => source.defects = true,
+            "noise" => source.noise = value.parse().map_err(|_| invalid(option))?,
+            "size" => {
+                let mut dims = value.splitn(2, 'x').map(|dim| dim.parse::<u32>());
+                match (dims.next(), dims.next()) {
+                    (Some(Ok(width)), Some(Ok(height))) if width > 0 && height > 0 => {
+                        source.width = width;
+                        source.height = height;
+                    }
+                    _ => return Err(invalid(option)),
+                }
+            }
+            _ => return Err(invalid(option)),
+        }
+    }
+
+    return Ok(Box::new(source));
+}
+
+/// Opens a frame source from its spec (e.g. "v4l2:/dev/video0" or "synthetic:defects").
+pub fn open(spec: &str) -> Result<Box<dyn FrameSource>> {
+    let mut parts = spec.trim().splitn(2, ':');
+    let kind = parts.next().unwrap_or_default();
+    let arg = parts.next().unwrap_or_default();
+
+    let source = match kind {
+        "v4l2" if arg.is_empty() => open_camera("/dev/video0")?,
+        "v4l2"      => open_camera(arg)?,
+        "file"      => Box::new(FileSource::open(arg)?),
+        "dir"       => Box::new(DirSource::open(arg)?),
+        "synthetic" => open_synthetic(arg)?,
+        _ => return Err(Error::Source(format!("unknown frame source '{}'", spec))),
+    };
+
+    return Ok(source);
+}
+
+/// Returns "data/capture" directory path (as path buffer).
+pub fn get_path() -> PathBuf {
+    let mut path = aux::get_path();
+    path.push("capture");
+    return path;
+}
+
+/// Saves a grabbed frame into "data/capture" directory, named by the current time.
+/// Returns the path of the saved file.
+pub fn save_frame(frame: &DynamicImage) -> Result<String> {
+    let file_name = format!("frame_{}.png", Local::now().format("%Y%m%d_%H%M%S_%3f"));
+    let path = get_path().join(file_name).to_string_lossy().to_string();
+
+    imgfx::save(frame, &path)?;
+
+    return Ok(path);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GenericImageView, GrayImage};
+
+    /// Returns the gray levels of a synthetic frame (luma conversion may round them down).
+    fn levels(frame: &DynamicImage) -> GrayImage {
+        let rgb = frame.to_rgb8();
+        return GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| image::Luma([rgb.get_pixel(x, y)[0]]));
+    }
+
+    #[test]
+    fn parses_synthetic_specs() {
+        let mut source = open("synthetic:defects, noise=4, size=120x80").unwrap();
+        assert_eq!(source.name(), "synthetic 120x80");
+        assert_eq!(source.grab().unwrap().dimensions(), (120, 80));
+
+        assert_eq!(open("synthetic").unwrap().name(), "synthetic 600x800");
+        for spec in ["synthetic:size=0x80", "synthetic:size=120", "synthetic:noise=loud", "synthetic:blur"].iter() {
+            assert!(matches!(open(spec), Err(Error::Source(_))), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_sources() {
+        assert!(matches!(open("usb:0"), Err(Error::Source(_))));
+        assert!(matches!(open("file:/nonexistent/capt.png"), Err(Error::Missing(_))));
+    }
+
+    #[cfg(not(feature = "v4l2"))]
+    #[test]
+    fn camera_needs_v4l2_feature() {
+        assert!(matches!(open("v4l2:/dev/video0"), Err(Error::Source(_))));
+    }
+
+    #[test]
+    fn clean_pattern_is_static() {
+        let mut source = SyntheticSource::new(120, 80);
+        let first = source.grab().unwrap();
+        let second = source.grab().unwrap();
+
+        assert_eq!(first.as_bytes(), second.as_bytes());
+        assert_eq!(levels(&first).get_pixel(0, 1)[0], 110);
+        assert_eq!(levels(&first).get_pixel(1, 1)[0], 150);
+    }
+
+    #[test]
+    fn noise_stays_within_amplitude() {
+        let clean = levels(&SyntheticSource::new(120, 80).grab().unwrap());
+        let mut source = SyntheticSource{ noise: 4, ..SyntheticSource::new(120, 80) };
+        let noisy = levels(&source.grab().unwrap());
+
+        let diffs: Vec<i32> = clean.pixels().zip(noisy.pixels())
+            .map(|(a, b)| b[0] as i32 - a[0] as i32)
+            .collect();
+        assert!(diffs.iter().all(|diff| diff.abs() <= 4));
+        assert!(diffs.iter().any(|&diff| diff != 0));
+        assert_ne!(noisy.as_raw(), levels(&source.grab().unwrap()).as_raw());
+    }
+
+    #[test]
+    fn defects_move_between_frames() {
+        let mut source = SyntheticSource{ defects: true, ..SyntheticSource::new(120, 80) };
+        let first = levels(&source.grab().unwrap());
+        let second = levels(&source.grab().unwrap());
+
+        let dark = |img: &GrayImage| img.pixels().filter(|pix| pix[0] == 40).count();
+        assert!(dark(&first) > 0);
+        assert_eq!(dark(&first), dark(&second));
+        assert_ne!(first.as_raw(), second.as_raw());
+    }
+}
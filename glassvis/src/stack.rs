@@ -0,0 +1,236 @@
+//! Frame stacking module for Glassvis.
+//!
+//! Single camera frames carry sensor noise that shows up as false diffs. Merging
+//! several frames of the same panel into one capture suppresses it: the `Mean`
+//! averages every pixel, the `Median` ignores outliers such as hot pixels or a
+//! passing reflection, and the `SigmaClip` mean averages only the values close to
+//! the mean of a pixel. Frames are grabbed from a frame source or opened from files
+//! and merged per color channel (alpha is dropped).
+
+use image::{DynamicImage, GenericImageView, RgbImage};
+
+use crate::error::{check_dimensions, Error, Result};
+use crate::imgfx;
+use crate::source::FrameSource;
+
+/// Values farther than this many standard deviations from the mean are clipped.
+const CLIP_SIGMA: f32 = 2.0;
+
+/// Maximum number of clipping rounds of the sigma-clipped mean.
+const CLIP_ROUNDS: usize = 3;
+
+/// Represents the available frame merge methods.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Mean,
+    Median,
+    SigmaClip,
+}
+
+impl Method {
+    /// Parses a merge method from its settings panel identifier.
+    pub fn from_id(id: &str) -> Method {
+        let method = match id {
+            "mean"   => Method::Mean,
+            "median" => Method::Median,
+            "sigma"  => Method::SigmaClip,
+            _        => Method::Mean,
+        };
+
+        return method;
+    }
+
+    /// Returns the settings panel identifier of a merge method.
+    pub fn id(&self) -> &'static str {
+        let id = match self {
+            Method::Mean      => "mean",
+            Method::Median    => "median",
+            Method::SigmaClip => "sigma",
+        };
+
+        return id;
+    }
+}
+
+/// Returns the mean of values.
+fn mean(values: &[u8]) -> f32 {
+    let sum: u32 = values.iter().map(|&value| value as u32).sum();
+    return sum as f32 / values.len() as f32;
+}
+
+/// Returns the median of values (sorts them in place).
+fn median(values: &mut [u8]) -> f32 {
+    values.sort_unstable();
+
+    let mid = values.len() / 2;
+    if values.len() % 2 == 0 {
+        return (values[mid - 1] as f32 + values[mid] as f32) / 2.0;
+    }
+    return values[mid] as f32;
+}
+
+/// Returns the mean of the values within `CLIP_SIGMA` standard deviations of the mean,
+/// repeating the clipping until no value is dropped.
+fn sigma_clip(values: &[u8]) -> f32 {
+    let mut kept: Vec<u8> = values.to_vec();
+    let mut avg = mean(&kept);
+
+    for _ in 0..CLIP_ROUNDS {
+        let var = kept.iter().map(|&value| (value as f32 - avg).powi(2)).sum::<f32>() / kept.len() as f32;
+        let limit = CLIP_SIGMA * var.sqrt();
+        let clipped: Vec<u8> = kept.iter().cloned().filter(|&value| (value as f32 - avg).abs() <= limit).collect();
+
+        if clipped.len() == kept.len() || clipped.is_empty() {
+            break;
+        }
+
+        kept = clipped;
+        avg = mean(&kept);
+    }
+
+    return avg;
+}
+
+/// Merges frames of the same size into one frame.
+pub fn merge(frames: &[DynamicImage], method: Method) -> Result<DynamicImage> {
+    let first = frames.first().ok_or(Error::Missing(String::from("frames to merge")))?;
+
+    if frames.len() == 1 {
+        return Ok(DynamicImage::ImageRgb8(first.to_rgb8()));
+    }
+
+    for frame in frames.iter() {
+        check_dimensions(first.dimensions(), frame.dimensions())?;
+    }
+
+    let frames: Vec<RgbImage> = frames.iter().map(|frame| frame.to_rgb8()).collect();
+    let (width, height) = first.dimensions();
+    let mut dst = RgbImage::new(width, height);
+    let mut values = vec![0u8; frames.len()];
+
+    for (x, y, pix) in dst.enumerate_pixels_mut() {
+        for c in 0..3 {
+            for (value, frame) in values.iter_mut().zip(frames.iter()) {
+                *value = frame.get_pixel(x, y)[c];
+            }
+
+            let merged = match method {
+                Method::Mean      => mean(&values),
+                Method::Median    => median(&mut values),
+                Method::SigmaClip => sigma_clip(&values),
+            };
+
+            pix[c] = merged.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    return Ok(DynamicImage::ImageRgb8(dst));
+}
+
+/// Grabs consecutive frames of a frame source and merges them into one frame.
+pub fn grab(source: &mut dyn FrameSource, count: usize, method: Method) -> Result<DynamicImage> {
+    let mut frames = Vec::with_capacity(count);
+
+    for _ in 0..count.max(1) {
+        frames.push(source.grab()?);
+    }
+
+    return merge(&frames, method);
+}
+
+/// Opens image files of the same panel and merges them into one frame.
+pub fn open(paths: &[String], method: Method) -> Result<DynamicImage> {
+    let mut frames = Vec::with_capacity(paths.len());
+
+    for path in paths.iter() {
+        frames.push(imgfx::open(path)?);
+    }
+
+    return merge(&frames, method);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, Rgba, RgbaImage};
+
+    use crate::source::SyntheticSource;
+
+    /// Creates a uniform RGB frame of given size and level.
+    fn frame(width: u32, height: u32, level: u8) -> DynamicImage {
+        return DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([level, level, level])));
+    }
+
+    #[test]
+    fn mean_of_values() {
+        assert_eq!(mean(&[10, 20, 40]), 70.0 / 3.0);
+        assert_eq!(mean(&[255, 255]), 255.0);
+    }
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&mut [30, 10, 20]), 20.0);
+        assert_eq!(median(&mut [40, 10, 30, 20]), 25.0);
+        assert_eq!(median(&mut [7]), 7.0);
+    }
+
+    #[test]
+    fn sigma_clip_rejects_outliers() {
+        // one hot pixel among ten frames
+        let values = [100, 101, 99, 100, 100, 102, 98, 100, 100, 255];
+        assert!((sigma_clip(&values) - 100.0).abs() < 0.01);
+        assert!(mean(&values) > 115.0);
+
+        // equal values have nothing to clip
+        assert_eq!(sigma_clip(&[50, 50, 50]), 50.0);
+    }
+
+    #[test]
+    fn merges_frames_per_pixel() {
+        let mut hot = RgbImage::from_pixel(4, 4, Rgb([100, 100, 100]));
+        hot.put_pixel(1, 1, Rgb([255, 255, 255]));
+        let mut frames = vec![frame(4, 4, 100); 9];
+        frames.push(DynamicImage::ImageRgb8(hot));
+
+        let merged = merge(&frames, Method::SigmaClip).unwrap().to_rgb8();
+        assert_eq!(merged.get_pixel(1, 1), &Rgb([100, 100, 100]));
+
+        let merged = merge(&frames, Method::Mean).unwrap().to_rgb8();
+        assert_eq!(merged.get_pixel(1, 1), &Rgb([116, 116, 116]));
+        assert_eq!(merged.get_pixel(0, 0), &Rgb([100, 100, 100]));
+    }
+
+    #[test]
+    fn merge_drops_alpha_of_single_frames() {
+        let src = DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 2, Rgba([10, 20, 30, 0])));
+        let merged = merge(&[src], Method::Median).unwrap();
+
+        assert_eq!(merged.to_rgb8().get_pixel(0, 0), &Rgb([10, 20, 30]));
+        assert!(matches!(merged, DynamicImage::ImageRgb8(_)));
+    }
+
+    #[test]
+    fn merge_rejects_mismatched_frames() {
+        let frames = vec![frame(4, 4, 100), frame(4, 3, 100)];
+        assert!(matches!(merge(&frames, Method::Mean), Err(Error::DimensionMismatch{ .. })));
+        assert!(matches!(merge(&[], Method::Mean), Err(Error::Missing(_))));
+    }
+
+    #[test]
+    fn merging_grabbed_frames_suppresses_noise() {
+        let clean = SyntheticSource::new(40, 30).grab().unwrap().to_rgb8();
+        let mut source = SyntheticSource::new(40, 30);
+        source.noise = 8;
+
+        // mean absolute deviation of the red channel from the clean pattern
+        let error = |frame: DynamicImage| {
+            let rgb = frame.to_rgb8();
+            let sum: i32 = rgb.pixels().zip(clean.pixels()).map(|(p, q)| (p[0] as i32 - q[0] as i32).abs()).sum();
+            sum as f32 / (40.0 * 30.0)
+        };
+
+        let single = error(grab(&mut source, 1, Method::Mean).unwrap());
+        let merged = error(grab(&mut source, 9, Method::Mean).unwrap());
+        assert!(merged < single / 2.0, "{} >= {} / 2", merged, single);
+    }
+}
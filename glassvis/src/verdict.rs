@@ -0,0 +1,335 @@
+//! Verdict module for Glassvis.
+//!
+//! An inspection is judged against the limits of its recipe: the defect rate, the
+//! number of defects of each class, the area of single defects, defects inside
+//! forbidden zones and the zone grades. Breaking a limit fails the panel, while
+//! coming close to one (above the review fraction of the limit) or a failed
+//! alignment marks it for review by an operator. Limits left out of a recipe do not
+//! apply, so a recipe without limits passes every panel:
+//!
+//! ```toml
+//! [limits]
+//! max_rate = 0.05
+//! max_area = 40
+//! forbidden_zones = ["vision"]
+//! review = 0.8
+//!
+//! [limits.max_count]
+//! scratch = 0
+//! bubble = 3
+//! ```
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::classify::DefectClass;
+use crate::error::{Error, Result};
+use crate::{Inspection, Settings};
+
+/// Represents the verdict of an inspection, from best to worst.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Verdict {
+    Pass,
+    Review,
+    Fail,
+}
+
+impl Verdict {
+    /// Returns the display name of a verdict.
+    pub fn name(&self) -> &'static str {
+        let name = match self {
+            Verdict::Pass   => "PASS",
+            Verdict::Review => "REVIEW",
+            Verdict::Fail   => "FAIL",
+        };
+
+        return name;
+    }
+}
+
+/// Represents the acceptance limits of a product.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Maximum defect rate in percent (none = unlimited).
+    pub max_rate: Option<f32>,
+    /// Maximum number of defects of a class, by class identifier (missing = unlimited).
+    pub max_count: BTreeMap<String, u32>,
+    /// Maximum area of a single defect in pixels (none = unlimited).
+    pub max_area: Option<u32>,
+    /// Names of the zones that must not hold any defect.
+    pub forbidden_zones: Vec<String>,
+    /// Fraction of a limit above which a passing panel is marked for review (1 = never).
+    pub review: f32,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        return Limits{
+            max_rate: None,
+            max_count: BTreeMap::new(),
+            max_area: None,
+            forbidden_zones: Vec::new(),
+            review: 1.0,
+        };
+    }
+}
+
+impl Limits {
+    /// Checks that the limits are in range and name known defect classes.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(max_rate) = self.max_rate {
+            if max_rate.is_finite() == false || max_rate < 0.0 {
+                return Err(Error::Recipe(format!("max_rate {} must not be negative", max_rate)));
+            }
+        }
+        if self.review.is_finite() == false || self.review < 0.0 || self.review > 1.0 {
+            return Err(Error::Recipe(format!("review {} is out of range 0-1", self.review)));
+        }
+
+        // class limits are keyed by class identifier
+        for class in self.max_count.keys() {
+            if DefectClass::from_id(class).is_none() {
+                return Err(Error::Recipe(format!("unknown defect class '{}' in limits", class)));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Represents a broken (or nearly broken) rule.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub verdict: Verdict,
+    pub rule: String,
+}
+
+/// Represents the verdict of an inspection along with the rules that caused it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Judgement {
+    pub verdict: Verdict,
+    pub findings: Vec<Finding>,
+}
+
+impl Judgement {
+    /// Records a rule with its verdict, keeping the worst verdict.
+    fn add(&mut self, verdict: Verdict, rule: String) {
+        if verdict == Verdict::Pass {
+            return;
+        }
+
+        self.verdict = self.verdict.max(verdict);
+        self.findings.push(Finding{ verdict: verdict, rule: rule });
+    }
+}
+
+/// Returns the rules that caused a verdict, separated by semicolons.
+pub fn summary(verdict: Verdict, findings: &Vec<Finding>) -> String {
+    let rules: Vec<&str> = findings.iter()
+        .filter(|finding| finding.verdict == verdict)
+        .map(|finding| finding.rule.as_str())
+        .collect();
+
+    return rules.join("; ");
+}
+
+/// Grades a measured value against its limit.
+fn grade(value: f32, limit: f32, review: f32) -> Verdict {
+    if value > limit {
+        return Verdict::Fail;
+    }
+
+    if review < 1.0 && limit > 0.0 && value > limit * review {
+        return Verdict::Review;
+    }
+
+    return Verdict::Pass;
+}
+
+/// Judges an inspection run with given settings against the limits of a product.
+pub fn judge(inspection: &Inspection, settings: &Settings, limits: &Limits) -> Judgement {
+    let mut judgement = Judgement{
+        verdict: Verdict::Pass,
+        findings: Vec::new(),
+    };
+
+    let review = limits.review;
+
+    // results of a misaligned capture can not be trusted
+    if let Some(err) = &inspection.alignment_error {
+        judgement.add(Verdict::Review, err.clone());
+    } else if settings.align && inspection.alignment.is_none() {
+        judgement.add(Verdict::Review, String::from("alignment failed"));
+    }
+
+    if let Some(max_rate) = limits.max_rate {
+        let rate = inspection.defect_rate();
+        judgement.add(
+            grade(rate, max_rate, review),
+            format!("defect rate {:.4}% (limit {}%)", rate, max_rate),
+        );
+    }
+
+    for class in DefectClass::ALL.iter() {
+        let max = match limits.max_count.get(class.id()) {
+            Some(max) => *max,
+            None => continue,
+        };
+
+        let count = inspection.defects.iter().filter(|defect| defect.class == *class).count();
+        judgement.add(
+            grade(count as f32, max as f32, review),
+            format!("{} {} defects (limit {})", count, class.name(), max),
+        );
+    }
+
+    for defect in inspection.defects.iter() {
+        if let Some(max) = limits.max_area {
+            judgement.add(
+                grade(defect.area as f32, max as f32, review),
+                format!("defect {} area {} px (limit {} px)", defect.id, defect.area, max),
+            );
+        }
+
+        if let Some(zone) = &defect.zone {
+            if limits.forbidden_zones.contains(zone) {
+                judgement.add(Verdict::Fail, format!("defect {} in forbidden zone {}", defect.id, zone));
+            }
+        }
+    }
+
+    for zone in inspection.grades.iter() {
+        if zone.passed() == false {
+            judgement.add(
+                Verdict::Fail,
+                format!("zone {}: {} defects ({} allowed)", zone.name, zone.defects, zone.max_defects),
+            );
+        }
+    }
+
+    return judgement;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, GrayImage};
+    use imageproc::rect::Rect;
+
+    use crate::classify::Features;
+    use crate::imgfx::Defect;
+    use crate::zone::Grade;
+
+    /// Creates an inspection of 100 pixels with given defect pixel count and defects.
+    fn inspection(counter: u32, defects: Vec<Defect>) -> Inspection {
+        return Inspection{
+            image: DynamicImage::new_luma8(10, 10),
+            mask: GrayImage::new(10, 10),
+            defects: defects,
+            counter: counter,
+            area: 100,
+            grades: Vec::new(),
+            score: None,
+            alignment: None,
+            alignment_error: None,
+        };
+    }
+
+    /// Creates a defect of given class and area.
+    fn defect(id: u32, class: DefectClass, area: u32, zone: Option<&str>) -> Defect {
+        return Defect{
+            id: id,
+            rect: Rect::at(0, 0).of_size(1, 1),
+            area: area,
+            centroid: (0.0, 0.0),
+            mean_contrast: 100.0,
+            max_contrast: 100,
+            zone: zone.map(String::from),
+            features: Features::default(),
+            class: class,
+        };
+    }
+
+    /// Creates limits allowing a 5% defect rate with a review fraction of 0.8.
+    fn limits() -> Limits {
+        return Limits{ max_rate: Some(5.0), review: 0.8, ..Limits::default() };
+    }
+
+    #[test]
+    fn rate_boundaries() {
+        let settings = Settings::default();
+
+        assert_eq!(judge(&inspection(4, Vec::new()), &settings, &limits()).verdict, Verdict::Pass);
+        assert_eq!(judge(&inspection(5, Vec::new()), &settings, &limits()).verdict, Verdict::Review);
+        assert_eq!(judge(&inspection(6, Vec::new()), &settings, &limits()).verdict, Verdict::Fail);
+    }
+
+    #[test]
+    fn review_fraction_of_one_never_reviews() {
+        let limits = Limits{ review: 1.0, ..limits() };
+        assert_eq!(judge(&inspection(5, Vec::new()), &Settings::default(), &limits).verdict, Verdict::Pass);
+    }
+
+    #[test]
+    fn zero_count_limit_fails_any_defect() {
+        let mut limits = limits();
+        limits.max_count.insert(String::from("scratch"), 0);
+        let settings = Settings::default();
+
+        let clean = inspection(1, vec![defect(1, DefectClass::Spot, 1, None)]);
+        let scratched = inspection(1, vec![defect(1, DefectClass::Scratch, 1, None)]);
+        let judgement = judge(&scratched, &settings, &limits);
+
+        assert_eq!(judge(&clean, &settings, &limits).verdict, Verdict::Pass);
+        assert_eq!(judgement.verdict, Verdict::Fail);
+        assert_eq!(summary(Verdict::Fail, &judgement.findings), "1 scratch defects (limit 0)");
+    }
+
+    #[test]
+    fn area_and_forbidden_zone_limits() {
+        let limits = Limits{ max_area: Some(10), forbidden_zones: vec![String::from("vision")], ..limits() };
+        let settings = Settings::default();
+
+        let at_limit = inspection(1, vec![defect(1, DefectClass::Spot, 10, Some("band"))]);
+        let over = inspection(1, vec![defect(1, DefectClass::Spot, 11, Some("band"))]);
+        let forbidden = inspection(1, vec![defect(1, DefectClass::Spot, 1, Some("vision"))]);
+
+        assert_eq!(judge(&at_limit, &settings, &limits).verdict, Verdict::Review);
+        assert_eq!(judge(&over, &settings, &limits).verdict, Verdict::Fail);
+        assert_eq!(judge(&forbidden, &settings, &limits).verdict, Verdict::Fail);
+    }
+
+    #[test]
+    fn failed_zone_grade_fails() {
+        let mut failed = inspection(0, Vec::new());
+        failed.grades.push(Grade{ name: String::from("vision"), region: None, area: 100, counter: 2, defects: 2, max_defects: 1 });
+
+        let judgement = judge(&failed, &Settings::default(), &limits());
+        assert_eq!(judgement.verdict, Verdict::Fail);
+        assert_eq!(summary(Verdict::Fail, &judgement.findings), "zone vision: 2 defects (1 allowed)");
+    }
+
+    #[test]
+    fn failed_alignment_needs_review() {
+        let aligned = Settings{ align: true, ..Settings::default() };
+
+        let mut failed = inspection(0, Vec::new());
+        failed.alignment_error = Some(String::from("too few matches"));
+        let judgement = judge(&failed, &aligned, &limits());
+        assert_eq!(judgement.verdict, Verdict::Review);
+        assert_eq!(summary(Verdict::Review, &judgement.findings), "too few matches");
+
+        let missing = judge(&inspection(0, Vec::new()), &aligned, &limits());
+        assert_eq!(summary(Verdict::Review, &missing.findings), "alignment failed");
+        assert_eq!(judge(&inspection(0, Vec::new()), &Settings::default(), &limits()).verdict, Verdict::Pass);
+    }
+
+    #[test]
+    fn missing_limits_pass_any_defects() {
+        let defects = vec![defect(1, DefectClass::Scratch, 500, Some("vision"))];
+        assert_eq!(judge(&inspection(50, defects), &Settings::default(), &Limits::default()).verdict, Verdict::Pass);
+    }
+}
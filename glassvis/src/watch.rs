@@ -0,0 +1,150 @@
+//! Hot folder module for Glassvis.
+//!
+//! A hot folder is a directory another program (e.g. the line camera software)
+//! writes captured images into. It is polled for image files that appeared since
+//! it was opened. A new file is only reported once its size stayed the same for
+//! two polls in a row, so that files still being written are not read half-way.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::aux;
+use crate::error::{Error, Result};
+
+/// Represents a watched directory.
+pub struct HotFolder {
+    dir: PathBuf,
+    /// Files already reported or skipped.
+    seen: HashSet<PathBuf>,
+    /// New files along with their size at the previous poll.
+    pending: HashMap<PathBuf, u64>,
+}
+
+impl HotFolder {
+    /// Opens a directory as hot folder. Image files already inside are ignored.
+    pub fn open(dir: &Path) -> Result<HotFolder> {
+        if dir.is_dir() == false {
+            return Err(Error::Missing(format!("hot folder at '{}'", dir.display())));
+        }
+
+        let folder = HotFolder{
+            dir: dir.to_path_buf(),
+            seen: aux::list_images(dir)?.into_iter().collect(),
+            pending: HashMap::new(),
+        };
+
+        return Ok(folder);
+    }
+
+    /// Returns the watched directory.
+    pub fn dir(&self) -> &Path {
+        return &self.dir;
+    }
+
+    /// Marks a file as seen, so that it is never reported (e.g. a result written into the folder).
+    pub fn skip(&mut self, path: &Path) {
+        self.pending.remove(path);
+        self.seen.insert(path.to_path_buf());
+    }
+
+    /// Returns the image files that were completely written since the last poll, in name order.
+    pub fn poll(&mut self) -> Result<Vec<PathBuf>> {
+        let mut ready = Vec::new();
+
+        for path in aux::list_images(&self.dir)? {
+            if self.seen.contains(&path) {
+                continue;
+            }
+
+            // the file may have been removed in the meantime
+            let size = match fs::metadata(&path) {
+                Ok(meta) => meta.len(),
+                Err(_) => continue,
+            };
+
+            if size > 0 && self.pending.get(&path) == Some(&size) {
+                self.pending.remove(&path);
+                self.seen.insert(path.clone());
+                ready.push(path);
+            } else {
+                self.pending.insert(path, size);
+            }
+        }
+
+        return Ok(ready);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a fresh empty directory in the temp directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("glassvis-watch-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        return dir;
+    }
+
+    #[test]
+    fn reports_files_after_two_polls_of_same_size() {
+        let dir = temp_dir("stable");
+        let mut folder = HotFolder::open(&dir).unwrap();
+
+        fs::write(dir.join("panel.png"), b"12345").unwrap();
+        assert!(folder.poll().unwrap().is_empty());
+        assert_eq!(folder.poll().unwrap(), vec![dir.join("panel.png")]);
+        assert!(folder.poll().unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn waits_while_files_grow() {
+        let dir = temp_dir("growing");
+        let mut folder = HotFolder::open(&dir).unwrap();
+
+        fs::write(dir.join("panel.png"), b"1").unwrap();
+        assert!(folder.poll().unwrap().is_empty());
+        fs::write(dir.join("panel.png"), b"12").unwrap();
+        assert!(folder.poll().unwrap().is_empty());
+        fs::write(dir.join("panel.png"), b"123").unwrap();
+        assert!(folder.poll().unwrap().is_empty());
+        assert_eq!(folder.poll().unwrap(), vec![dir.join("panel.png")]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ignores_files_present_at_open() {
+        let dir = temp_dir("existing");
+        fs::write(dir.join("old.png"), b"12345").unwrap();
+        let mut folder = HotFolder::open(&dir).unwrap();
+
+        assert!(folder.poll().unwrap().is_empty());
+        assert!(folder.poll().unwrap().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn never_reports_skipped_files() {
+        let dir = temp_dir("skipped");
+        let mut folder = HotFolder::open(&dir).unwrap();
+
+        // skipped before it is written, and after it was seen once
+        folder.skip(&dir.join("diff_a.png"));
+        fs::write(dir.join("diff_a.png"), b"12345").unwrap();
+        fs::write(dir.join("diff_b.png"), b"12345").unwrap();
+        assert!(folder.poll().unwrap().is_empty());
+        folder.skip(&dir.join("diff_b.png"));
+
+        for _ in 0..3 {
+            assert!(folder.poll().unwrap().is_empty());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
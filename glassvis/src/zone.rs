@@ -0,0 +1,361 @@
+//! Zone module for Glassvis.
+//!
+//! Glass standards grade areas of a panel differently, e.g. the central vision
+//! area (A) more strictly than the edge band (B). A zone has its own tolerances
+//! and is graded on its own. Each pixel belongs to the first zone whose region
+//! contains it, and a zone without region covers the rest of the image. Pixels
+//! outside all zones use the global settings and are not graded.
+
+use image::{GrayImage, Luma, Rgba, RgbaImage};
+use imageproc::contrast::threshold as threshold_level;
+use imageproc::map::map_pixels;
+use imageproc::region_labelling::{connected_components, Connectivity};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::imgfx::{Defect, DiffMode};
+use crate::roi::Shape;
+use crate::Settings;
+
+/// Zone outline colors, cycled by zone order.
+const COLORS: [[u8; 3]; 4] = [[0, 160, 255], [255, 0, 255], [0, 220, 160], [255, 220, 0]];
+
+/// Represents a named zone with its own tolerances.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Zone {
+    pub name: String,
+    pub significance: u8,
+    /// Color difference threshold (ΔE modes only), the global one if not set.
+    pub delta_e: Option<f32>,
+    pub min_area: u32,
+    /// Number of defects allowed in the zone.
+    pub max_defects: u32,
+    /// Zone area, the rest of the image if not set.
+    pub region: Option<Shape>,
+}
+
+impl Default for Zone {
+    fn default() -> Zone {
+        return Zone{
+            name: String::new(),
+            significance: 10,
+            delta_e: None,
+            min_area: 1,
+            max_defects: 0,
+            region: None,
+        };
+    }
+}
+
+impl Zone {
+    /// Checks that the zone tolerances are in range (a zero significance would divide by zero).
+    pub fn validate(&self) -> Result<()> {
+        if self.significance == 0 || self.significance > 100 {
+            return Err(Error::Recipe(format!(
+                "significance {} of zone '{}' is out of range 1-100", self.significance, self.name
+            )));
+        }
+        if let Some(delta_e) = self.delta_e {
+            if delta_e.is_finite() == false || delta_e <= 0.0 {
+                return Err(Error::Recipe(format!("delta_e {} of zone '{}' must be positive", delta_e, self.name)));
+            }
+        }
+
+        return Ok(());
+    }
+}
+
+/// Represents the grading result of a zone.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Grade {
+    pub name: String,
+    pub region: Option<Shape>,
+    /// Number of inspected pixels in the zone.
+    pub area: u32,
+    /// Number of defect pixels in the zone.
+    pub counter: u32,
+    pub defects: u32,
+    pub max_defects: u32,
+}
+
+impl Grade {
+    /// Checks if the zone holds no more defects than allowed.
+    pub fn passed(&self) -> bool {
+        return self.defects <= self.max_defects;
+    }
+}
+
+/// Returns the tolerances (significance, ΔE threshold, min area) of a zone index (0 = no zone).
+fn tolerances(zones: &Vec<Zone>, settings: &Settings, index: usize) -> (u8, f32, u32) {
+    if index == 0 {
+        return (settings.significance, settings.delta_e, settings.filter.min_area);
+    }
+
+    let zone = &zones[index - 1];
+    return (zone.significance, zone.delta_e.unwrap_or(settings.delta_e), zone.min_area);
+}
+
+/// Returns the loosest significance and ΔE threshold of the global settings and all zones.
+pub fn loosest(settings: &Settings) -> (u8, f32) {
+    let mut significance = settings.significance;
+    let mut delta_e = settings.delta_e;
+
+    for index in 1..=settings.zones.len() {
+        let (sig, de, _) = tolerances(&settings.zones, settings, index);
+        significance = significance.max(sig);
+        delta_e = delta_e.min(de);
+    }
+
+    return (significance, delta_e);
+}
+
+/// Renders a zone map whose pixels hold the zone index (0 = no zone).
+pub fn render(zones: &Vec<Zone>, width: u32, height: u32) -> GrayImage {
+    let mut map = GrayImage::new(width, height);
+
+    // paint later zones first so that earlier zones win
+    for (i, zone) in zones.iter().enumerate().rev() {
+        let index = (i + 1).min(255) as u8;
+
+        match &zone.region {
+            Some(shape) => shape.fill(&mut map, index),
+            None => map = GrayImage::from_pixel(width, height, Luma([index])),
+        }
+    }
+
+    return map;
+}
+
+/// Drops the diff pixels below the significance (or ΔE threshold) of their zone.
+pub fn threshold(mask: &GrayImage, map: &GrayImage, settings: &Settings, mode: DiffMode) -> GrayImage {
+    let dst = map_pixels(mask, |x, y, p: Luma<u8>| {
+        if p[0] == 0 {
+            return p;
+        }
+
+        let index = map.get_pixel(x, y)[0] as usize;
+        let (significance, delta_e, _) = tolerances(&settings.zones, settings, index);
+
+        // ΔE contrast is rounded, so allow half a unit of slack
+        let passed = match mode {
+            DiffMode::Luma | DiffMode::Ssim => p[0] > 255 / significance,
+            _ => p[0] as f32 + 0.5 > delta_e,
+        };
+
+        if passed {
+            return p;
+        }
+        return Luma([0u8]);
+    });
+
+    return dst;
+}
+
+/// Removes the defects smaller than the minimum area of their zone. Defects are
+/// labelled on the whole mask, so a defect crossing a zone boundary is judged as a
+/// whole by the zone holding its centroid.
+pub fn remove_small(mask: &GrayImage, map: &GrayImage, settings: &Settings) -> GrayImage {
+    let (width, height) = mask.dimensions();
+    let labels = connected_components(&threshold_level(mask, 0), Connectivity::Eight, Luma([0u8]));
+
+    // area and coordinate sums of each labelled defect
+    let mut sums: Vec<(u32, u64, u64)> = Vec::new();
+    for (x, y, label) in labels.enumerate_pixels() {
+        let id = label[0] as usize;
+        while sums.len() <= id {
+            sums.push((0, 0, 0));
+        }
+        sums[id] = (sums[id].0 + 1, sums[id].1 + x as u64, sums[id].2 + y as u64);
+    }
+
+    let small: Vec<bool> = sums.iter().map(|&(area, sum_x, sum_y)| {
+        if area == 0 {
+            return false;
+        }
+
+        let x = ((sum_x as f32 / area as f32).round() as u32).min(width - 1);
+        let y = ((sum_y as f32 / area as f32).round() as u32).min(height - 1);
+        let (_, _, min_area) = tolerances(&settings.zones, settings, map.get_pixel(x, y)[0] as usize);
+
+        return area < min_area;
+    }).collect();
+
+    let dst = map_pixels(mask, |x, y, p: Luma<u8>| {
+        let id = labels.get_pixel(x, y)[0] as usize;
+        if id > 0 && small[id] {
+            return Luma([0u8]);
+        }
+        return p;
+    });
+
+    return dst;
+}
+
+/// Returns the zone index of a defect, located by its centroid.
+pub fn locate(defect: &Defect, map: &GrayImage) -> usize {
+    let (width, height) = map.dimensions();
+    let x = (defect.centroid.0.round() as u32).min(width - 1);
+    let y = (defect.centroid.1.round() as u32).min(height - 1);
+
+    return map.get_pixel(x, y)[0] as usize;
+}
+
+/// Grades each zone by its defect count.
+pub fn grade(
+    settings: &Settings,
+    map: &GrayImage,
+    roi: &GrayImage,
+    mask: &GrayImage,
+    defects: &Vec<Defect>,
+) -> Vec<Grade> {
+    let mut grades: Vec<Grade> = settings.zones.iter().map(|zone| {
+        Grade{
+            name: zone.name.clone(),
+            region: zone.region.clone(),
+            area: 0,
+            counter: 0,
+            defects: 0,
+            max_defects: zone.max_defects,
+        }
+    }).collect();
+
+    for (x, y, p) in map.enumerate_pixels() {
+        let index = p[0] as usize;
+        if index == 0 || index > grades.len() || roi.get_pixel(x, y)[0] == 0 {
+            continue;
+        }
+
+        let grade = &mut grades[index - 1];
+        grade.area += 1;
+        if mask.get_pixel(x, y)[0] > 0 {
+            grade.counter += 1;
+        }
+    }
+
+    for defect in defects.iter() {
+        let index = locate(defect, map);
+        if index > 0 && index <= grades.len() {
+            grades[index - 1].defects += 1;
+        }
+    }
+
+    return grades;
+}
+
+/// Outlines the zone regions, cycling through the zone colors.
+pub fn draw(dst: &mut RgbaImage, grades: &Vec<Grade>) {
+    for (i, grade) in grades.iter().enumerate() {
+        let c = COLORS[i % COLORS.len()];
+
+        if let Some(shape) = &grade.region {
+            shape.outline(dst, Rgba([c[0], c[1], c[2], 255]));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::imgfx;
+
+    /// Creates a zone with a significance, allowed defect count and optional rectangle.
+    fn zone(name: &str, significance: u8, max_defects: u32, rect: Option<(i32, i32, u32, u32)>) -> Zone {
+        return Zone{
+            name: name.to_string(),
+            significance: significance,
+            max_defects: max_defects,
+            region: rect.map(|(x, y, width, height)| Shape::Rect{ x: x, y: y, width: width, height: height }),
+            ..Zone::default()
+        };
+    }
+
+    #[test]
+    fn earlier_zones_win() {
+        let zones = vec![
+            zone("vision", 10, 0, Some((0, 0, 10, 10))),
+            zone("band", 10, 0, Some((5, 5, 10, 10))),
+            zone("rest", 10, 0, None),
+        ];
+        let map = render(&zones, 20, 20);
+
+        assert_eq!(map.get_pixel(7, 7)[0], 1);
+        assert_eq!(map.get_pixel(12, 12)[0], 2);
+        assert_eq!(map.get_pixel(18, 2)[0], 3);
+    }
+
+    #[test]
+    fn pixels_outside_all_zones_use_global_settings() {
+        let zones = vec![zone("vision", 50, 0, Some((0, 0, 10, 10)))];
+        let map = render(&zones, 20, 20);
+        let settings = Settings{ significance: 10, zones: zones, ..Settings::default() };
+
+        // level 255 / 50 = 5 inside the zone, 255 / 10 = 25 outside
+        let mut mask = GrayImage::new(20, 20);
+        mask.put_pixel(2, 2, Luma([20]));
+        mask.put_pixel(15, 15, Luma([20]));
+        mask.put_pixel(16, 16, Luma([30]));
+        let kept = threshold(&mask, &map, &settings, DiffMode::Luma);
+
+        assert_eq!(kept.get_pixel(2, 2)[0], 20);
+        assert_eq!(kept.get_pixel(15, 15)[0], 0);
+        assert_eq!(kept.get_pixel(16, 16)[0], 30);
+        assert_eq!(loosest(&settings).0, 50);
+    }
+
+    #[test]
+    fn grades_defects_by_zone() {
+        let zones = vec![
+            zone("vision", 10, 0, Some((0, 0, 10, 20))),
+            zone("band", 10, 1, None),
+        ];
+        let map = render(&zones, 20, 20);
+        let roi = GrayImage::from_pixel(20, 20, Luma([255]));
+        let settings = Settings{ zones: zones, ..Settings::default() };
+
+        let mut mask = GrayImage::new(20, 20);
+        for &(x, y) in [(2, 2), (2, 3), (14, 4), (14, 14)].iter() {
+            mask.put_pixel(x, y, Luma([100]));
+        }
+        let defects = imgfx::segment(&mask);
+        let grades = grade(&settings, &map, &roi, &mask, &defects);
+
+        assert_eq!((grades[0].area, grades[0].counter, grades[0].defects), (200, 2, 1));
+        assert_eq!((grades[1].area, grades[1].counter, grades[1].defects), (200, 2, 2));
+        assert!(grades[0].passed() == false);
+        assert!(grades[1].passed() == false);
+    }
+
+    #[test]
+    fn rejects_zero_significance() {
+        assert!(matches!(zone("vision", 0, 0, None).validate(), Err(Error::Recipe(_))));
+        assert!(zone("vision", 100, 0, None).validate().is_ok());
+    }
+
+    #[test]
+    fn judges_straddling_defects_as_a_whole() {
+        let zones = vec![
+            Zone{ min_area: 1, ..zone("vision", 10, 0, Some((0, 0, 10, 20))) },
+            Zone{ min_area: 4, ..zone("band", 10, 0, None) },
+        ];
+        let map = render(&zones, 20, 20);
+        let settings = Settings{ zones: zones, ..Settings::default() };
+
+        // 7 px line from x = 6 to 12, its centroid (9, 5) lies in the vision zone
+        let mut mask = GrayImage::new(20, 20);
+        for x in 6..13 {
+            mask.put_pixel(x, 5, Luma([100]));
+        }
+        let kept = remove_small(&mask, &map, &settings);
+        assert_eq!(kept, mask);
+
+        // the band zone holding the centroid drops the whole line
+        let mut strict = settings.clone();
+        strict.zones[0].region = Some(Shape::Rect{ x: 0, y: 0, width: 8, height: 20 });
+        strict.zones[1].min_area = 8;
+        let map = render(&strict.zones, 20, 20);
+        let kept = remove_small(&mask, &map, &strict);
+        assert!(kept.pixels().all(|p| p[0] == 0));
+    }
+}
@@ -6,14 +6,13 @@ use gio;
 use gtk;
 
 use gio::prelude::*;
-use gtk::prelude::*;
 
 mod ui;
 
 /// Runs main GTK application loop.
 fn main() {
     let application =
-        gtk::Application::new("org.bitbucket.glassvis", gio::ApplicationFlags::empty())
+        gtk::Application::new(Some("org.bitbucket.glassvis"), gio::ApplicationFlags::empty())
             .expect("Failed to load Glassvis!");
 
     application.connect_startup(move |app| {
@@ -1,10 +1,15 @@
 //! UI module for Glassvis.
 
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::string::String;
+use std::sync::mpsc;
+use std::thread;
 
 use cairo;
 use gdk;
 use gio;
+use glib;
 use gtk;
 
 use gio::prelude::*;
@@ -12,26 +17,130 @@ use gtk::prelude::*;
 
 use gtk::{
     AboutDialog, Adjustment, ApplicationWindow, Box, Builder, Button,
-    ColorButton, DrawingArea, EventBox, FileChooserAction,
-    FileChooserDialog, FileFilter, Grid, Image, InfoBar,
-    Label, MessageType, ResponseType, Scale, Switch,
+    ColorButton, ComboBoxText, CssProvider, Dialog, DrawingArea, EventBox, FileChooserAction,
+    FileChooserButton, FileChooserDialog, FileFilter, Grid, Image, InfoBar,
+    Label, ListStore, MessageType, ProgressBar, ResponseType, Scale, StyleContext, Switch, TreeView,
 };
 
 use cairo::{Context};
-use gdk_pixbuf::{InterpType, Pixbuf};
+use gdk_pixbuf::{Colorspace, InterpType, Pixbuf};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+
+use glassvis::history::{self, Filter};
+use glassvis::report::{self, Images, Input};
+use glassvis::{
+    aux, imgfx, recipe, roi, source, stack, verdict, Alignment, Batch, DefectClass, Error, History, Inspection,
+    HotFolder, Output, Recipe, References, Report, Result, Roi, Settings, Shape, Summary, Verdict,
+};
+
+mod state;
+
+use state::{
+    BatchRun, Grabber, Live, LiveDone, LiveJob, Loaded, Outcome, Pane, RoiTool, State, Store, Watch, MIN_ZOOM, ZOOM_STEP,
+};
+
+/// Hot folder polling interval in milliseconds.
+const WATCH_INTERVAL: u32 = 500;
+
+/// Live inspection frame interval in milliseconds.
+const LIVE_INTERVAL: u32 = 50;
+
+/// Info panel colors of the verdicts.
+const VERDICT_CSS: &str = "
+    infobar.pass, infobar.pass > revealer > box { background-color: #339a4d; color: #ffffff; }
+    infobar.review, infobar.review > revealer > box { background-color: #e68a00; color: #ffffff; }
+    infobar.fail, infobar.fail > revealer > box { background-color: #cc3333; color: #ffffff; }
+";
+
+/// Style classes of the verdicts.
+const VERDICT_CLASSES: [&str; 3] = ["pass", "review", "fail"];
 
-mod aux;
-mod imgfx;
+/// Colors info panel by a verdict, or resets its color if none.
+fn set_verdict_style(info_panel: &InfoBar, verdict: Option<Verdict>) {
+    let style = info_panel.get_style_context();
+
+    for class in VERDICT_CLASSES.iter() {
+        style.remove_class(class);
+    }
+
+    if let Some(verdict) = verdict {
+        style.add_class(&verdict.name().to_lowercase());
+    }
+}
 
 /// Displays info about defect rate.
-fn display_info(info_panel: &InfoBar, defect_info: &Label, width: u32, height: u32, ndiff: u32) {
-    let dim = width * height / 100;
-    let defect_rate = ndiff as f32 / dim as f32;
+fn display_info(defect_info: &Label, inspection: &Inspection) {
+    let defect_rate = inspection.defect_rate();
+    let mut msg = format!("Total Defect Rate = {}%  ({} defects)", defect_rate, inspection.defects.len());
+    
+    if let Some(index) = inspection.score {
+        msg = format!("{}  SSIM = {:.4}", msg, index);
+    }
+    
+    // number of defects of each class
+    let classes: Vec<String> = inspection.class_counts().iter()
+        .map(|(class, count)| format!("{} {}", count, class.name()))
+        .collect();
+    
+    if classes.is_empty() == false {
+        msg = format!("{}  [{}]", msg, classes.join(", "));
+    }
+    
+    // grade of each zone as found/allowed defects
+    for grade in inspection.grades.iter() {
+        let mark = if grade.passed() { "OK" } else { "NG" };
+        msg = format!("{}  |  Zone {}: {}/{} {}", msg, grade.name, grade.defects, grade.max_defects, mark);
+    }
+    
+    defect_info.set_text(&msg);
+}
+
+/// Displays the verdict along with the rules that caused it, coloring info panel accordingly.
+fn display_verdict(info_panel: &InfoBar, defect_info: &Label, report: &Report) {
+    let msg_type = match report.verdict {
+        Verdict::Pass   => MessageType::Info,
+        Verdict::Review => MessageType::Warning,
+        Verdict::Fail   => MessageType::Error,
+    };
+
+    info_panel.set_message_type(msg_type);
+    set_verdict_style(info_panel, Some(report.verdict));
+
+    let mut msg = format!("{}  Verdict = {}", defect_info.get_text(), report.verdict.name());
+    let rules = verdict::summary(report.verdict, &report.findings);
+
+    if rules.is_empty() == false {
+        msg = format!("{}  ({})", msg, rules);
+    }
+
+    defect_info.set_text(&msg);
+}
+
+/// Displays an error message inside info panel.
+fn display_error(info_panel: &InfoBar, defect_info: &Label, err: &Error) {
     info_panel.set_message_type(MessageType::Error);
-    let msg = format!("Total Defect Rate = {}%", defect_rate);
+    set_verdict_style(info_panel, None);
+    let msg = format!("Error: {}", err);
     defect_info.set_text(&msg);
 }
 
+/// Displays info about the estimated image alignment.
+fn display_alignment(align_info: &Label, alignment: Option<&Alignment>, error: Option<&str>) {
+    let msg = match alignment {
+        Some(a) => {
+            let m = a.matrix;
+            format!(
+                "H = [{:.3} {:.3} {:.1}; {:.3} {:.3} {:.1}; {:.5} {:.5} 1]  RMS = {:.2}px  ({}/{} inliers)",
+                m[0], m[1], m[2], m[3], m[4], m[5], m[6], m[7], a.residual, a.inliers, a.matches,
+            )
+        }
+        None => format!("{}!", error.unwrap_or("Alignment failed")),
+    };
+    
+    align_info.set_text(&msg);
+}
+
 /// Displays info about click event position.
 fn display_position(info_panel: &InfoBar, pos_info: &Label, xp: i32, yp: i32) {
     info_panel.set_message_type(MessageType::Info);
@@ -115,38 +224,260 @@ fn draw_right_ruler(ruler: &DrawingArea, ctx: &Context) -> Inhibit {
 }
 
 /// Processes input images in rgba color mode and finds their diffs.
-fn proc_img(input: &str) -> String {
-    let mut src = imgfx::open(&input);
+fn proc_img(input: &str) -> Result<Loaded> {
+    let mut src = imgfx::open(input)?;
+    let original = src.dimensions();
     src = imgfx::resize(src, 600, 800);
     let prefix = "_";
-    let path = aux::set_path(&prefix, &input);
-    let dst = path.to_str().unwrap();
-    imgfx::save(&src, &dst);
-    let output = dst.to_string();
+    let path = aux::set_path(prefix, input)?;
+    let output = path.to_string_lossy().to_string();
+    imgfx::save(&src, &output)?;
+
+    let loaded = Loaded{
+        path: output,
+        source: input.to_string(),
+        image: src,
+        original: original,
+    };
+
+    return Ok(loaded);
+}
+
+/// Returns a copy of a ROI mask file resized to the given dimensions in "data/output" directory.
+/// A copy newer than the mask file is reused.
+fn scaled_mask(mask: &str, size: (u32, u32)) -> Result<String> {
+    let path = aux::set_path("roi_", mask)?;
+    let output = path.to_string_lossy().to_string();
+
+    let copied = fs::metadata(&path).and_then(|meta| meta.modified());
+    let changed = fs::metadata(mask).and_then(|meta| meta.modified());
+    let fresh = match (copied, changed) {
+        (Ok(copied), Ok(changed)) => copied >= changed && image::image_dimensions(&path).ok() == Some(size),
+        _ => false,
+    };
+
+    if fresh == false {
+        let src = imgfx::open(mask)?;
+        imgfx::save(&src.resize_exact(size.0, size.1, FilterType::Nearest), &output)?;
+    }
+
+    return Ok(output);
+}
+
+/// Returns the settings scaled from original image coordinates onto a processed image.
+fn scaled_settings(settings: &Settings, loaded: &Loaded) -> Result<Settings> {
+    let (sx, sy) = loaded.scale();
+    let mut scaled = settings.scaled(sx, sy);
+
+    if let Some(mask) = &settings.roi.mask {
+        scaled.roi.mask = Some(scaled_mask(mask, loaded.image.dimensions())?);
+    }
+
+    return Ok(scaled);
+}
+
+/// Converts a dynamic image into a pixbuf scaled by the given zoom factor.
+fn to_pixbuf(src: &DynamicImage, zoom: f64) -> Option<Pixbuf> {
+    let rgba = src.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let (w, h) = (width as i32, height as i32);
+    let pixbuf = Pixbuf::from_mut_slice(rgba.into_raw(), Colorspace::Rgb, true, 8, w, h, w * 4);
+
+    if zoom == 1.0 {
+        return Some(pixbuf);
+    }
+
+    let zoom_width = (w as f64 * zoom).round().max(1.0) as i32;
+    let zoom_height = (h as f64 * zoom).round().max(1.0) as i32;
 
-    return output;
+    return pixbuf.scale_simple(zoom_width, zoom_height, InterpType::Hyper);
+}
+
+/// Renders the image shown in a pane into its image view widget.
+/// The reference pane also shows the region of interest.
+fn render_view(img: &Image, state: &State, pane: Pane) {
+    let src = match state.shown(pane) {
+        Some(src) => src,
+        None => return,
+    };
+
+    let roi = &state.recipe.settings.roi;
+    let mut overlay = None;
+
+    // shapes are kept in original image coordinates
+    if pane == Pane::Reference && (roi.is_empty() == false || state.draft.is_empty() == false) {
+        if let Some(reference) = &state.reference {
+            let (sx, sy) = reference.scale();
+            let draft = roi::scale_points(&state.draft, sx, sy);
+            overlay = scaled_settings(&state.recipe.settings, reference)
+                .and_then(|settings| roi::overlay(src, &settings.roi, &draft))
+                .ok();
+        }
+    }
+
+    if let Some(pixbuf) = to_pixbuf(overlay.as_ref().unwrap_or(src), state.zoom) {
+        img.set_from_pixbuf(Some(&pixbuf));
+    }
+}
+
+/// Converts a click position inside an image view widget into image coordinates.
+fn to_image_coords(img: &Image, zoom: f64, xp: f64, yp: f64) -> Option<[i32; 2]> {
+    let pixbuf = img.get_pixbuf()?;
+    let (width, height) = (pixbuf.get_width(), pixbuf.get_height());
+
+    // image view widget centers its pixbuf
+    let dx = ((img.get_allocated_width() - width) / 2).max(0) as f64;
+    let dy = ((img.get_allocated_height() - height) / 2).max(0) as f64;
+
+    let max_x = (width as f64 / zoom) as i32 - 1;
+    let max_y = (height as f64 / zoom) as i32 - 1;
+    let x = (((xp - dx) / zoom).round() as i32).max(0).min(max_x);
+    let y = (((yp - dy) / zoom).round() as i32).max(0).min(max_y);
+
+    return Some([x, y]);
+}
+
+/// Converts a click position inside the reference view into original image coordinates.
+fn to_original_coords(img: &Image, state: &State, xp: f64, yp: f64) -> Option<[i32; 2]> {
+    let point = to_image_coords(img, state.zoom, xp, yp)?;
+    let (sx, sy) = state.reference.as_ref()?.scale();
+
+    return Some(roi::scale_points(&[point], 1.0 / sx, 1.0 / sy)[0]);
+}
+
+/// Handles reference image button press event while drawing region-of-interest shapes.
+fn roi_pressed(store: &Store, ref_img: &Image, evt_btn: &gdk::EventButton) {
+    let (xp, yp) = evt_btn.get_position();
+    let (tool, point) = {
+        let state = store.get();
+        (state.roi_tool, to_original_coords(ref_img, &state, xp, yp))
+    };
+
+    let point = match point {
+        Some(point) => point,
+        None => return,
+    };
+
+    match tool {
+        RoiTool::None => {}
+        RoiTool::IncludeRect | RoiTool::ExcludeRect => {
+            store.update(|state| state.draft = vec![point]);
+        }
+        RoiTool::IncludePolygon | RoiTool::ExcludePolygon => {
+            // right or double click closes the polygon
+            let closing = evt_btn.get_button() == 3
+                || evt_btn.get_event_type() == gdk::EventType::DoubleButtonPress;
+
+            store.update(|state| {
+                if closing == false {
+                    state.draft.push(point);
+                    return;
+                }
+
+                let points: Vec<[i32; 2]> = state.draft.drain(..).collect();
+                if points.len() >= 3 {
+                    state.add_shape(Shape::Polygon{ points: points });
+                }
+            });
+        }
+    }
+}
+
+/// Handles reference image button release event while drawing region-of-interest shapes.
+fn roi_released(store: &Store, ref_img: &Image, evt_btn: &gdk::EventButton) {
+    let (xp, yp) = evt_btn.get_position();
+    let (tool, start, end) = {
+        let state = store.get();
+        (state.roi_tool, state.draft.first().cloned(), to_original_coords(ref_img, &state, xp, yp))
+    };
+
+    if tool != RoiTool::IncludeRect && tool != RoiTool::ExcludeRect {
+        return;
+    }
+
+    let (start, end) = match (start, end) {
+        (Some(start), Some(end)) => (start, end),
+        _ => return,
+    };
+
+    store.update(|state| {
+        state.draft.clear();
+        state.add_shape(Shape::rect_between(start, end));
+    });
+}
+
+/// Renders the latest inspection result or error inside info panel.
+fn render_info(info_panel: &InfoBar, defect_info: &Label, align_info: &Label, state: &State) {
+    if let Some(err) = &state.error {
+        display_error(info_panel, defect_info, err);
+        return;
+    }
+
+    let outcome = match &state.outcome {
+        Some(outcome) => outcome,
+        None => {
+            set_verdict_style(info_panel, None);
+            defect_info.set_text("");
+            align_info.set_text("");
+            return;
+        }
+    };
+
+    // calculate defect rate and display it inside info panel
+    display_info(defect_info, &outcome.inspection);
+
+    if let Some((bright, dark)) = outcome.polarity {
+        let msg = format!("{}  [{} bright, {} dark]", defect_info.get_text(), bright, dark);
+        defect_info.set_text(&msg);
+    }
+
+    display_verdict(info_panel, defect_info, &outcome.report);
+
+    if let Some(warning) = &outcome.warning {
+        let msg = format!("{}  |  {}", defect_info.get_text(), warning);
+        defect_info.set_text(&msg);
+    }
+
+    if let Some(live) = &state.live {
+        let msg = format!("{}  |  Live: {} frames, {:.1} fps", defect_info.get_text(), live.frames, live.rate());
+        defect_info.set_text(&msg);
+    }
+
+    if outcome.settings.align == true {
+        let inspection = &outcome.inspection;
+        display_alignment(align_info, inspection.alignment.as_ref(), inspection.alignment_error.as_deref());
+    } else {
+        align_info.set_text("");
+    }
 }
 
 /// Handles about button click event.
 fn about_btn_clicked() {
     let dialog = AboutDialog::new();
     dialog.set_program_name("Glassvis");
-    dialog.set_version("Version 1.0");
-    dialog.set_comments("Computer vision application for visual quality control.");
-    dialog.set_website("...");
-    dialog.set_license("Copyright © 2019 Koray Eyinç");
+    dialog.set_version(Some("Version 1.0"));
+    dialog.set_comments(Some("Computer vision application for visual quality control."));
+    dialog.set_website(Some("..."));
+    dialog.set_license(Some("Copyright © 2019 Koray Eyinç"));
     dialog.show();
     dialog.run();
     dialog.hide();
 }
 
-/// Handles image button click event.
-fn image_btn_clicked(window: &ApplicationWindow, img: &Image) -> String {
+/// Handles image button click event. With a merge method, several image files of the same
+/// panel can be selected and are merged into one image in "data/capture" directory.
+/// Returns the processed image, or none if the dialog was cancelled.
+fn image_btn_clicked(window: &ApplicationWindow, merge: Option<stack::Method>) -> Result<Option<Loaded>> {
     let dialog = FileChooserDialog::new(Some("Open Image"), Some(window), FileChooserAction::Open);
 
     dialog.add_buttons(&[
-        ("Open", ResponseType::Ok.into()),
-        ("Cancel", ResponseType::Cancel.into()),
+        ("Open", ResponseType::Ok),
+        ("Cancel", ResponseType::Cancel),
     ]);
 
     let path = aux::get_path();
@@ -165,82 +496,783 @@ fn image_btn_clicked(window: &ApplicationWindow, img: &Image) -> String {
 
     dialog.set_filter(&file_filter);
     dialog.set_current_folder(path);
-    dialog.set_select_multiple(false);
-    dialog.run();
+    dialog.set_select_multiple(merge.is_some());
 
-    let dia = dialog.clone();
+    let resp_type = dialog.run();
+    let file_names = dialog.get_filenames();
+    dialog.close();
 
     // handle 'cancel' button click event
-    dialog.connect_response(move |_, resp_type| {
-        if resp_type == ResponseType::Cancel.into() {
-            dia.destroy();
-            return;
+    if resp_type != ResponseType::Ok || file_names.is_empty() {
+        return Ok(None);
+    }
+
+    let inputs: Vec<String> = file_names.iter()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .collect();
+
+    // check if inputs are image files
+    for input in inputs.iter() {
+        if aux::is_image_file(input) == false {
+            return Err(Error::Missing(format!("supported image file at '{}'", input)));
         }
-    });
+    }
+
+    let input = match merge {
+        Some(method) if inputs.len() > 1 => source::save_frame(&stack::open(&inputs, method)?)?,
+        _ => inputs[0].clone(),
+    };
 
-    let file_name = dialog.get_filename().unwrap();
-    let input = file_name.to_str().unwrap();
-    let mut output = String::from("");
+    let loaded = proc_img(&input)?;
 
-    // check if input is an image file
-    if aux::is_image_file(input) {
-        output = proc_img(input);
-        img.set_property_file(Some(output.as_str()));
+    return Ok(Some(loaded));
+}
+
+/// Handles camera button click event by grabbing (and merging) frames from the selected frame
+/// source into "data/capture" directory. Falls back to the file dialog if the source is "dialog".
+/// Returns the processed frame, or none if the dialog was cancelled.
+fn camera_btn_clicked(window: &ApplicationWindow, store: &Store) -> Result<Option<Loaded>> {
+    let (spec, method) = {
+        let state = store.get();
+        (state.source_spec.clone(), state.stack_method)
+    };
+
+    if spec.is_empty() || spec == "dialog" {
+        return image_btn_clicked(window, Some(method));
+    }
+
+    let frame = store.modify(|state| state.grab_merged())?;
+    let input = source::save_frame(&frame)?;
+    let loaded = proc_img(&input)?;
+
+    return Ok(Some(loaded));
+}
+
+/// Returns the path of a result file made from a loaded image: next to its original file
+/// when watching a hot folder with results beside the input, otherwise in "data/output".
+fn result_path(state: &State, prefix: &str, loaded: &Loaded) -> Result<PathBuf> {
+    match &state.watch {
+        Some(watch) if watch.beside_input => aux::set_path_beside(&format!("{}_", prefix), &loaded.source),
+        _ => aux::set_path(prefix, &loaded.path),
     }
+}
+
+/// Records an inspection in the history database. Returns the reason if recording
+/// failed, so that the inspection result is still shown (or counted in a batch).
+fn record_history(report: &Report, product: &str, image: &str) -> Option<String> {
+    let recorded = History::open(&history::get_path()).and_then(|mut history| history.record(report, product, image));
 
-    dialog.destroy();
-    return output;
+    return recorded.err().map(|err| format!("History not recorded: {}", err));
 }
 
 /// Handles diff button click event.
-fn diff_btn_clicked(
-    info_panel: &InfoBar,
-    defect_info: &Label,
-    ref_img: &Image,
-    capt_img: &Image,
-    significance: u8,
-    bound_state: bool,
-) {
-    let ref_img_file = ref_img.get_property_file().unwrap();
-    let ref_img_path = ref_img_file.as_str().to_string();
+fn diff_btn_clicked(state: &State) -> Result<Outcome> {
+    let reference = state.reference.as_ref()
+        .ok_or(Error::Missing(String::from("reference image")))?;
+
+    let capture = state.capture.as_ref()
+        .ok_or(Error::Missing(String::from("captured image")))?;
+
+    // find, filter and segment diff pixels, with the recipe shapes scaled onto the processed images
+    let settings = scaled_settings(&state.recipe.settings, capture)?;
+    let inspection = glassvis::compare(&reference.image, &capture.image, &settings)?;
+
+    let prefix = "diff";
+    let path = result_path(state, prefix, capture)?;
+    let diff_path = path.to_string_lossy().to_string();
+
+    // mark diff pixels and save them
+    let diff = glassvis::annotate(&inspection, &state.recipe.output)?;
+    glassvis::save(&diff, &diff_path)?;
+
+    // save the machine-readable result and defect list next to the marked image
+    let judgement = verdict::judge(&inspection, &settings, &state.recipe.limits);
+    let report = Report::new(&inspection, &state.recipe.settings, Some(&reference.source), &capture.source, &judgement)?;
+    report.save_json(&path.with_extension("json").to_string_lossy())?;
+    report.save_csv(&path.with_extension("csv").to_string_lossy())?;
+
+    // record the inspection in the history database
+    let warning = record_history(&report, &state.recipe.name, &diff_path);
+
+    let outcome = Outcome{
+        inspection: inspection,
+        image: diff,
+        pane: Pane::Capture,
+        settings: state.recipe.settings.clone(),
+        polarity: None,
+        report: report,
+        warning: warning,
+    };
+
+    return Ok(outcome);
+}
 
-    let capt_img_file = capt_img.get_property_file().unwrap();
-    let mut capt_img_path = capt_img_file.as_str().to_string();
+/// Detects hotspots on the captured image, or on the reference image if nothing was captured.
+fn detect_spots(state: &State) -> Result<Outcome> {
+    // prefer the captured image and fall back to the reference image
+    let (target, pane) = match (&state.capture, &state.reference) {
+        (Some(capture), _) => (capture, Pane::Capture),
+        (None, Some(reference)) => (reference, Pane::Reference),
+        (None, None) => return Err(Error::Missing(String::from("image to inspect"))),
+    };
+
+    // find spots deviating from their local background
+    let settings = &scaled_settings(&state.recipe.settings, target)?;
+    let inspection = glassvis::detect(&target.image, settings)?;
+    let polarity = imgfx::spot_polarity(&target.image.to_luma8(), &inspection.defects, settings.spot_radius);
+
+    let prefix = "spot";
+    let path = result_path(state, prefix, target)?;
+    let spot_path = path.to_string_lossy().to_string();
+
+    // mark spot pixels and save them
+    let spots = glassvis::annotate(&inspection, &state.recipe.output)?;
+    glassvis::save(&spots, &spot_path)?;
+
+    let judgement = verdict::judge(&inspection, settings, &state.recipe.limits);
+    let report = Report::new(&inspection, &state.recipe.settings, None, &target.source, &judgement)?;
+    report.save_json(&path.with_extension("json").to_string_lossy())?;
+    report.save_csv(&path.with_extension("csv").to_string_lossy())?;
+
+    // record the inspection in the history database
+    let warning = record_history(&report, &state.recipe.name, &spot_path);
+
+    let outcome = Outcome{
+        inspection: inspection,
+        image: spots,
+        pane: pane,
+        settings: state.recipe.settings.clone(),
+        polarity: Some(polarity),
+        report: report,
+        warning: warning,
+    };
+
+    return Ok(outcome);
+}
+
+/// Fills the recipe selector with the recipe files of "data/recipes" directory.
+fn list_recipes(recipe_combo: &ComboBoxText) {
+    recipe_combo.remove_all();
 
-    // normalize captured image path if necessary
-    if capt_img_path.contains("diff") {
-        capt_img_path = ref_img_path.replace("_ref", "_capt");
+    for path in recipe::list(&recipe::get_path()) {
+        let id = path.to_string_lossy().to_string();
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        recipe_combo.append(Some(id.as_str()), &name);
     }
+}
 
-    let mut src = imgfx::open(&ref_img_path);
-    let mut dst = imgfx::open(&capt_img_path);
+/// Handles recipe selection change event.
+fn recipe_changed(recipe_combo: &ComboBoxText, settings_view: &SettingsView, store: &Store) -> Result<()> {
+    // the selector is emptied while listing recipes
+    let recipe_path = match recipe_combo.get_active_id() {
+        Some(id) => id.to_string(),
+        None => return Ok(()),
+    };
 
-    // mark diff pixels with specified color
-    let (diff, points, width, height, counter) = imgfx::mark_diffs(&mut src, &mut dst, significance);
+    let recipe = Recipe::load(&recipe_path)?;
 
-    let prefix = "diff";
-    let path = aux::set_path(&prefix, &capt_img_path);
-    let diff_path = path.to_str().unwrap().to_string();
-    
-    let key = String::from("diff_path");
-    let val = &diff_path;
-    aux::set_env(&key, val);
-    
-    let active = String::from("active");
-    aux::set_env(&active, val);
+    // keep fields without widgets (e.g. ROI), then let widgets mirror the rest
+    store.modify(|state| state.recipe = recipe.clone());
+    settings_view.write(&recipe);
+    store.update(|state| state.error = None);
+
+    return Ok(());
+}
+
+/// Handles recipe save button click event.
+fn recipe_save_btn_clicked(window: &ApplicationWindow, recipe_combo: &ComboBoxText, store: &Store) -> Result<()> {
+    let dialog = FileChooserDialog::new(Some("Save Recipe"), Some(window), FileChooserAction::Save);
+
+    dialog.add_buttons(&[
+        ("Save", ResponseType::Ok),
+        ("Cancel", ResponseType::Cancel),
+    ]);
+
+    let path = recipe::get_path();
+    fs::create_dir_all(&path)?;
+
+    // set file filter with supported recipe types
+    let file_filter = FileFilter::new();
+    file_filter.add_pattern("*.toml");
+    file_filter.add_pattern("*.json");
+
+    dialog.set_filter(&file_filter);
+    dialog.set_current_folder(path);
+    dialog.set_current_name("recipe.toml");
+    dialog.set_do_overwrite_confirmation(true);
+
+    let resp_type = dialog.run();
+    let file_name = dialog.get_filename();
+    dialog.close();
+
+    // handle 'cancel' button click event
+    if resp_type != ResponseType::Ok {
+        return Ok(());
+    }
+
+    let file_name = match file_name {
+        Some(file_name) => file_name,
+        None => return Ok(()),
+    };
+
+    let mut recipe = store.get().recipe.clone();
+    if let Some(stem) = file_name.file_stem() {
+        recipe.name = stem.to_string_lossy().to_string();
+    }
+
+    let output = file_name.to_string_lossy().to_string();
+    recipe.save(&output)?;
+
+    // select the saved recipe if it was saved into the recipe directory
+    list_recipes(recipe_combo);
+    if let Some(name) = file_name.file_name() {
+        let listed = recipe::get_path().join(name).to_string_lossy().to_string();
+        recipe_combo.set_active_id(Some(listed.as_str()));
+    }
+
+    return Ok(());
+}
 
-    if bound_state == true {
-        let rect  = imgfx::get_box(points);
-        dst = imgfx::draw_rect(diff, rect);
-        imgfx::save(&dst, &diff_path);
+/// Handles report button click event by exporting the latest result as a PDF or HTML report.
+fn report_btn_clicked(window: &ApplicationWindow, store: &Store) -> Result<()> {
+    // name the report after the inspected file
+    let stem = match &store.get().outcome {
+        Some(outcome) => aux::to_path(&outcome.report.capture.path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        None => return Err(Error::Missing(String::from("inspection result"))),
+    };
+
+    let dialog = FileChooserDialog::new(Some("Export Report"), Some(window), FileChooserAction::Save);
+
+    dialog.add_buttons(&[
+        ("Save", ResponseType::Ok),
+        ("Cancel", ResponseType::Cancel),
+    ]);
+
+    let mut path = aux::get_path();
+    path.push("output");
+    fs::create_dir_all(&path)?;
+
+    // set file filter with supported report types
+    let file_filter = FileFilter::new();
+    file_filter.add_pattern("*.pdf");
+    file_filter.add_pattern("*.html");
+
+    dialog.set_filter(&file_filter);
+    dialog.set_current_folder(path);
+    dialog.set_current_name(format!("report_{}.pdf", stem));
+    dialog.set_do_overwrite_confirmation(true);
+
+    let resp_type = dialog.run();
+    let file_name = dialog.get_filename();
+    dialog.close();
+
+    // handle 'cancel' button click event
+    if resp_type != ResponseType::Ok {
+        return Ok(());
+    }
+
+    let file_name = match file_name {
+        Some(file_name) => file_name,
+        None => return Ok(()),
+    };
+
+    let state = store.get();
+    let outcome = state.outcome.as_ref()
+        .ok_or(Error::Missing(String::from("inspection result")))?;
+
+    // hotspot detection has no reference image
+    let reference = match outcome.report.reference {
+        Some(_) => state.reference.as_ref().map(|loaded| &loaded.image),
+        None => None,
+    };
+
+    let images = Images{
+        reference: reference,
+        capture: &outcome.inspection.image,
+        annotated: &outcome.image,
+    };
+
+    // pick the report type by file extension
+    let output = file_name.to_string_lossy().to_string();
+    let is_html = file_name.extension().map(|ext| ext == "html" || ext == "htm").unwrap_or(false);
+
+    if is_html == true {
+        outcome.report.save_html(&images, &output)?;
     } else {
-        imgfx::save(diff, &diff_path);
+        outcome.report.save_pdf(&images, &output)?;
     }
-    
-    capt_img.set_from_file(aux::to_path(&diff_path));
-    
-    // calculate defect rate and display it inside info panel
-    display_info(info_panel, defect_info, width, height, counter);
+
+    return Ok(());
+}
+
+/// Reopens a recorded inspection by reloading its input images and rerunning it
+/// with the recorded settings. Returns the reference image, captured image and result.
+fn reopen(report: &Report, output: &Output) -> Result<(Option<Loaded>, Loaded, Outcome)> {
+    // the input files must not have changed since the inspection
+    let mut inputs = vec![&report.capture];
+    if let Some(reference) = &report.reference {
+        inputs.push(reference);
+    }
+
+    for input in inputs.iter() {
+        if report::hash_file(&input.path)? != input.sha256 {
+            return Err(Error::History(format!("'{}' changed since the inspection", input.path)));
+        }
+    }
+
+    let capture = proc_img(&report.capture.path)?;
+    let reference = match &report.reference {
+        Some(input) => Some(proc_img(&input.path)?),
+        None => None,
+    };
+
+    // hotspot detection has no reference image
+    let settings = &scaled_settings(&report.settings, &capture)?;
+    let (inspection, polarity) = match &reference {
+        Some(reference) => (glassvis::compare(&reference.image, &capture.image, settings)?, None),
+        None => {
+            let inspection = glassvis::detect(&capture.image, settings)?;
+            let polarity = imgfx::spot_polarity(&capture.image.to_luma8(), &inspection.defects, settings.spot_radius);
+            (inspection, Some(polarity))
+        }
+    };
+
+    let image = glassvis::annotate(&inspection, output)?;
+
+    let outcome = Outcome{
+        inspection: inspection,
+        image: image,
+        pane: Pane::Capture,
+        settings: report.settings.clone(),
+        polarity: polarity,
+        report: report.clone(),
+        warning: None,
+    };
+
+    return Ok((reference, capture, outcome));
+}
+
+/// Handles history button click event by browsing the recorded inspections
+/// and reopening the selected one into the image views.
+fn history_btn_clicked(history_view: &HistoryView, settings_view: &SettingsView, store: &Store) -> Result<()> {
+    let history = History::open(&history::get_path())?;
+    history_view.list_products(&history)?;
+    history_view.fill(&history)?;
+
+    let resp_type = history_view.dialog.run();
+    let selected = history_view.selected();
+    history_view.dialog.hide();
+
+    // handle 'close' button click event
+    if resp_type != ResponseType::Ok {
+        return Ok(());
+    }
+
+    let id = match selected {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+
+    let report = history.load(id)?;
+    let (reference, capture, outcome) = reopen(&report, &store.get().recipe.output)?;
+
+    // let widgets mirror the recorded settings
+    store.modify(|state| state.recipe.settings = report.settings.clone());
+    let recipe = store.get().recipe.clone();
+    settings_view.write(&recipe);
+
+    store.update(|state| {
+        state.reference = reference;
+        state.capture = Some(capture);
+        state.outcome = Some(outcome);
+        state.error = None;
+        state.zoom = 1.0;
+    });
+
+    return Ok(());
+}
+
+/// Loads a new hot folder file as captured image and inspects it.
+fn hot_folder_file_added(input: &str, store: &Store) -> Result<Outcome> {
+    let loaded = proc_img(input)?;
+    store.modify(|state| state.capture = Some(loaded));
+
+    let (outcome, marked) = {
+        let state = store.get();
+        let capture = state.capture.as_ref()
+            .ok_or(Error::Missing(String::from("captured image")))?;
+
+        if state.spot_detect == true {
+            (detect_spots(&state)?, result_path(&state, "spot", capture)?)
+        } else {
+            (diff_btn_clicked(&state)?, result_path(&state, "diff", capture)?)
+        }
+    };
+
+    // marked images written into the hot folder must not be inspected again
+    store.modify(|state| {
+        if let Some(watch) = &mut state.watch {
+            watch.folder.skip(&marked);
+        }
+    });
+
+    return Ok(outcome);
+}
+
+/// Inspects the image files that appeared in the watched hot folder since the last poll.
+fn hot_folder_polled(store: &Store) {
+    let polled = store.modify(|state| state.watch.as_mut().map(|watch| watch.folder.poll()));
+
+    let files = match polled {
+        Some(Ok(files)) => files,
+        Some(Err(err)) => {
+            store.update(|state| state.error = Some(err));
+            return;
+        }
+        None => return,
+    };
+
+    for file in files.iter() {
+        let result = hot_folder_file_added(&file.to_string_lossy(), store);
+
+        store.update(|state| {
+            match result {
+                Ok(outcome) => {
+                    state.outcome = Some(outcome);
+                    state.error = None;
+                }
+                Err(err) => state.error = Some(err),
+            }
+        });
+    }
+}
+
+/// Handles hot folder watch switch activation by watching the selected directory.
+fn hot_folder_watched(folder_btn: &FileChooserButton, results: &ComboBoxText, store: &Store) -> Result<()> {
+    let dir = folder_btn.get_filename()
+        .ok_or(Error::Missing(String::from("hot folder")))?;
+
+    let folder = HotFolder::open(&dir)?;
+    let beside_input = results.get_active_id().map(|id| id == "input").unwrap_or(false);
+
+    let store_poll = store.clone();
+    let timer = glib::timeout_add_local(WATCH_INTERVAL, move || {
+        hot_folder_polled(&store_poll);
+        Continue(true)
+    });
+
+    let watch = Watch{
+        folder: folder,
+        beside_input: beside_input,
+        timer: timer,
+    };
+
+    store.update(|state| {
+        state.watch = Some(watch);
+        state.error = None;
+    });
+
+    return Ok(());
+}
+
+/// Handles hot folder watch switch deactivation.
+fn hot_folder_unwatched(store: &Store) {
+    if let Some(watch) = store.modify(|state| state.watch.take()) {
+        glib::source_remove(watch.timer);
+    }
+}
+
+/// Inspects a capture of a batch, saving its marked image into "data/output" and recording it in the history.
+/// Returns the report along with the reason if recording failed.
+fn batch_capture(batch: &mut Batch, capture: &Path, recipe: &Recipe) -> Result<(Report, Option<String>)> {
+    let (inspection, report) = batch.inspect(capture, recipe)?;
+
+    let prefix = "diff_";
+    let path = aux::set_path(prefix, &capture.to_string_lossy())?;
+    let diff_path = path.to_string_lossy().to_string();
+
+    let diff = glassvis::annotate(&inspection, &recipe.output)?;
+    glassvis::save(&diff, &diff_path)?;
+
+    let warning = record_history(&report, &recipe.name, &diff_path);
+
+    return Ok((report, warning));
+}
+
+/// Inspects the next capture of the running batch. Returns false once the batch is done or stopped.
+fn batch_step(batch_view: &BatchView, store: &Store) -> bool {
+    let step = store.modify(|state| {
+        let recipe = &state.recipe;
+        let run = state.batch.as_mut()?;
+        let capture = run.batch.captures.get(run.next)?.clone();
+        let file = capture.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+
+        // a capture that can not be inspected does not stop the batch
+        let result = batch_capture(&mut run.batch, &capture, recipe);
+        match &result {
+            Ok((report, _)) => run.summary.add(&file, report),
+            Err(err) => run.summary.fail(&file, err),
+        }
+
+        run.next += 1;
+        Some((file, result, run.next, run.batch.captures.len()))
+    });
+
+    let (file, result, done, total) = match step {
+        Some(step) => step,
+        None => return false,
+    };
+
+    batch_view.add_row(&file, &result);
+    batch_view.progress.set_fraction(done as f64 / total as f64);
+    batch_view.progress.set_text(Some(format!("{}/{}  {}", done, total, file).as_str()));
+
+    if done < total {
+        return true;
+    }
+
+    if let Some(run) = store.modify(|state| state.batch.take()) {
+        batch_view.finish(&run.summary, &run.output);
+    }
+
+    return false;
+}
+
+/// Handles batch start button click event by inspecting the captures of the selected
+/// directory one after another while the dialog stays responsive.
+fn batch_start_btn_clicked(batch_view: &BatchView, store: &Store) -> Result<()> {
+    let dir = batch_view.dir.get_filename()
+        .ok_or(Error::Missing(String::from("capture directory")))?;
+
+    let references = batch_view.references(&store.get())?;
+    let batch = Batch::open(references, &dir)?;
+
+    let dir_name = dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let output = aux::set_path("batch_", &format!("{}.csv", dir_name))?.to_string_lossy().to_string();
+
+    batch_view.list.clear();
+    batch_view.progress.set_fraction(0.0);
+    batch_view.progress.set_text(Some(format!("0/{}", batch.captures.len()).as_str()));
+    batch_view.start_btn.set_sensitive(false);
+
+    store.modify(|state| {
+        state.batch = Some(BatchRun{
+            batch: batch,
+            next: 0,
+            summary: Summary::default(),
+            output: output,
+        });
+    });
+
+    let view_step = batch_view.clone();
+    let store_step = store.clone();
+    glib::idle_add_local(move || Continue(batch_step(&view_step, &store_step)));
+
+    return Ok(());
+}
+
+/// Handles batch button click event by showing the batch dialog.
+/// Closing the dialog stops a running batch.
+fn batch_btn_clicked(batch_view: &BatchView, store: &Store) {
+    batch_view.dialog.run();
+    batch_view.dialog.hide();
+
+    store.modify(|state| state.batch = None);
+    batch_view.start_btn.set_sensitive(true);
+}
+
+/// Grabs a frame from a frame source and inspects it against a reference image
+/// (or searches it for hotspots) without writing any files. Runs on the live worker thread.
+/// Returns the frame along with the result.
+fn live_frame(grabber: &mut Grabber, reference: Option<(DynamicImage, Input)>, recipe: &Recipe) -> Result<(Loaded, Outcome)> {
+    let frame = grabber.source.grab()?;
+    let original = frame.dimensions();
+
+    // frames must match the reference exactly, hotspot frames are fitted like loaded images
+    let image = match &reference {
+        Some((src, _)) => frame.resize_exact(src.width(), src.height(), FilterType::CatmullRom),
+        None => imgfx::resize(frame, 600, 800),
+    };
+
+    // the frame has no file until live inspection stops
+    let capture = Loaded{
+        path: String::new(),
+        source: grabber.source.name(),
+        image: image,
+        original: original,
+    };
+
+    let settings = &scaled_settings(&recipe.settings, &capture)?;
+    let frame = &capture.image;
+
+    // hotspot detection has no reference image
+    let (inspection, polarity, reference) = match reference {
+        Some((src, input)) => (glassvis::compare(&src, frame, settings)?, None, Some(input)),
+        None => {
+            let inspection = glassvis::detect(frame, settings)?;
+            let polarity = imgfx::spot_polarity(&frame.to_luma8(), &inspection.defects, settings.spot_radius);
+            (inspection, Some(polarity), None)
+        }
+    };
+
+    let image = glassvis::annotate(&inspection, &recipe.output)?;
+    let judgement = verdict::judge(&inspection, settings, &recipe.limits);
+    let input = Input::from_frame(&capture.source, frame);
+    let report = Report::from_inputs(&inspection, &recipe.settings, reference, input, &judgement);
+
+    let outcome = Outcome{
+        inspection: inspection,
+        image: image,
+        pane: Pane::Capture,
+        settings: recipe.settings.clone(),
+        polarity: polarity,
+        report: report,
+        warning: None,
+    };
+
+    return Ok((capture, outcome));
+}
+
+/// Inspects the frames queued by live inspection until the queue is dropped,
+/// handing each frame source back along with the result.
+fn live_worker(jobs: mpsc::Receiver<LiveJob>, done: glib::Sender<LiveDone>) {
+    for job in jobs.iter() {
+        let LiveJob{ mut grabber, reference, recipe } = job;
+        let result = live_frame(&mut grabber, reference, &recipe);
+
+        if done.send(LiveDone{ grabber: grabber, result: result }).is_err() {
+            return;
+        }
+    }
+}
+
+/// Hands the next live frame to the worker thread, unless a frame is still being inspected.
+fn live_tick(store: &Store) -> Result<()> {
+    store.modify(|state| {
+        let busy = state.live.as_ref().map(|live| live.busy).unwrap_or(true);
+        if busy {
+            return Ok(());
+        }
+
+        // describe the reference file before borrowing the live inspection
+        let reference = match (&state.reference, &mut state.live) {
+            (Some(reference), Some(live)) if state.spot_detect == false => {
+                Some((reference.image.clone(), live.reference(&reference.source)?))
+            }
+            (None, _) if state.spot_detect == false => {
+                return Err(Error::Missing(String::from("reference image")));
+            }
+            _ => None,
+        };
+
+        let job = LiveJob{
+            grabber: state.take_grabber()?,
+            reference: reference,
+            recipe: state.recipe.clone(),
+        };
+
+        if let Some(live) = &mut state.live {
+            live.busy = live.jobs.send(job).is_ok();
+        }
+
+        return Ok(());
+    })
+}
+
+/// Shows a frame inspected by the live worker thread, stopping live inspection on errors.
+fn live_done(done: LiveDone, live_switch: &Switch, store: &Store) {
+    let LiveDone{ grabber, result } = done;
+
+    // frames finished after live inspection stopped are dropped
+    let running = store.modify(|state| {
+        state.grabber = Some(grabber);
+        match &mut state.live {
+            Some(live) => { live.busy = false; true }
+            None => false,
+        }
+    });
+
+    if running == false {
+        return;
+    }
+
+    match result {
+        Ok((capture, outcome)) => store.update(|state| {
+            state.capture = Some(capture);
+            state.outcome = Some(outcome);
+            state.error = None;
+
+            if let Some(live) = &mut state.live {
+                live.frames += 1;
+            }
+        }),
+        Err(err) => {
+            live_switch.set_active(false);
+            store.update(|state| state.error = Some(err));
+        }
+    }
+}
+
+/// Handles live switch activation by streaming the frames of the selected frame source
+/// through the inspection on a worker thread.
+fn live_started(live_switch: &Switch, store: &Store) -> Result<()> {
+    let spec = store.get().source_spec.clone();
+
+    if spec.is_empty() || spec == "dialog" {
+        return Err(Error::Missing(String::from("frame source for live inspection")));
+    }
+
+    let (jobs, queue) = mpsc::channel();
+    let (done, results) = glib::MainContext::channel(glib::PRIORITY_DEFAULT);
+    thread::spawn(move || live_worker(queue, done));
+
+    let switch_done = live_switch.clone();
+    let store_done = store.clone();
+    results.attach(None, move |finished| {
+        live_done(finished, &switch_done, &store_done);
+        Continue(true)
+    });
+
+    let switch_tick = live_switch.clone();
+    let store_tick = store.clone();
+    let timer = glib::timeout_add_local(LIVE_INTERVAL, move || {
+        if let Err(err) = live_tick(&store_tick) {
+            switch_tick.set_active(false);
+            store_tick.update(|state| state.error = Some(err));
+        }
+        Continue(true)
+    });
+
+    store.update(|state| {
+        state.live = Some(Live::new(timer, jobs));
+        state.error = None;
+        state.zoom = 1.0;
+    });
+
+    return Ok(());
+}
+
+/// Handles live switch deactivation. The last frame is saved into "data/capture" directory
+/// so that it can be inspected and reported like any captured image.
+fn live_stopped(store: &Store) -> Result<()> {
+    if let Some(live) = store.modify(|state| state.live.take()) {
+        glib::source_remove(live.timer);
+    }
+
+    let frame = match &store.get().capture {
+        Some(capture) if capture.path.is_empty() => capture.image.clone(),
+        _ => return Ok(()),
+    };
+
+    let input = source::save_frame(&frame)?;
+    let loaded = proc_img(&input)?;
+
+    store.update(|state| state.capture = Some(loaded));
+
+    return Ok(());
 }
 
 /// Handles config button click event.
@@ -253,98 +1285,310 @@ fn config_btn_clicked(settings_panel: &Grid) {
 }
 
 /// Handles zoom-in button click event.
-fn zoom_in_btn_clicked(ref_img: &Image, capt_img: &Image, in_width: i32, in_height: i32) {
-    let mut ref_pixbuf: Pixbuf = ref_img.get_pixbuf().unwrap();
-    let ref_width = ref_pixbuf.get_width() + in_width;
-    let ref_height = ref_pixbuf.get_height() + in_height;
-
-    let mut capt_pixbuf: Pixbuf = capt_img.get_pixbuf().unwrap();
-    let capt_width = capt_pixbuf.get_width() + in_width;
-    let capt_height = capt_pixbuf.get_height() + in_height;
-    
-    // refresh pixbuf before zooming
-    zoom_fit_btn_clicked(ref_img, capt_img);
-    ref_pixbuf = ref_img.get_pixbuf().unwrap();
-    capt_pixbuf = capt_img.get_pixbuf().unwrap();
-    
-    ref_pixbuf = ref_pixbuf
-        .scale_simple(ref_width, ref_height, InterpType::Hyper)
-        .unwrap();
-    
-    capt_pixbuf = capt_pixbuf
-        .scale_simple(capt_width, capt_height, InterpType::Hyper)
-        .unwrap();
-    
-    ref_img.set_from_pixbuf(Some(&ref_pixbuf));
-    capt_img.set_from_pixbuf(Some(&capt_pixbuf));
+fn zoom_in_btn_clicked(store: &Store) {
+    store.update(|state| state.zoom += ZOOM_STEP);
 }
 
 /// Handles zoom-out button click event.
-fn zoom_out_btn_clicked(ref_img: &Image, capt_img: &Image, out_width: i32, out_height: i32) {
-    let mut ref_pixbuf: Pixbuf = ref_img.get_pixbuf().unwrap();
-    let ref_width = ref_pixbuf.get_width() - out_width;
-    let ref_height = ref_pixbuf.get_height() - out_height;
-    
-    let mut capt_pixbuf: Pixbuf = capt_img.get_pixbuf().unwrap();
-    let capt_width = capt_pixbuf.get_width() - out_width;
-    let capt_height = capt_pixbuf.get_height() - out_height;
-    
-    // refresh pixbuf before zooming
-    zoom_fit_btn_clicked(ref_img, capt_img);
-    ref_pixbuf = ref_img.get_pixbuf().unwrap();
-    capt_pixbuf = capt_img.get_pixbuf().unwrap();
-    
-    ref_pixbuf = ref_pixbuf
-        .scale_simple(ref_width, ref_height, InterpType::Hyper)
-        .unwrap();
-    
-    capt_pixbuf = capt_pixbuf
-        .scale_simple(capt_width, capt_height, InterpType::Hyper)
-        .unwrap();
-
-    ref_img.set_from_pixbuf(Some(&ref_pixbuf));
-    capt_img.set_from_pixbuf(Some(&capt_pixbuf));
+fn zoom_out_btn_clicked(store: &Store) {
+    store.update(|state| state.zoom = (state.zoom - ZOOM_STEP).max(MIN_ZOOM));
 }
 
 /// Handles zoom-fit button click event.
-fn zoom_fit_btn_clicked(ref_img: &Image, capt_img: &Image) {
-    let mut key = String::from("ref_path");
-    let ref_val = aux::get_env(&key);
-    let ref_path = aux::to_path(&ref_val);
-    
-    key = String::from("active");
-    let capt_val = aux::get_env(&key);
-    let capt_path = aux::to_path(&capt_val);
-
-    ref_img.set_from_file(ref_path);
-    capt_img.set_from_file(capt_path);
+fn zoom_fit_btn_clicked(store: &Store) {
+    store.update(|state| state.zoom = 1.0);
 }
 
 /// Handles fullscreen button click event.
-fn fullscreen_btn_clicked(fullscreen_btn: &Button, _window: &ApplicationWindow) {
-    let fullscreen = String::from("fullscreen");
-    let on  = String::from("on");
-    let off = String::from("off");
-    
-    if aux::get_env(&fullscreen) == off {
-        aux::set_env(&fullscreen, &on);
-        _window.fullscreen();
-    } else {
-        aux::set_env(&fullscreen, &off);
-        _window.unfullscreen();
-    }
+fn fullscreen_btn_clicked(store: &Store) {
+    store.update(|state| state.fullscreen = !state.fullscreen);
 }
 
 /// Handles app window -> quit button click event.
 fn quit(_window: &ApplicationWindow, _event: &gdk::Event) -> Inhibit {
-    _window.destroy();
+    // not inhibiting the event lets the window be destroyed
     Inhibit(false)
 }
 
+/// Converts a color channel value from 0.0-1.0 range to 0-255 range.
+fn to_channel(value: f64) -> u8 {
+    return (value * 255.0).round().clamp(0.0, 255.0) as u8;
+}
+
+/// Represents the settings panel widgets that make up the inspection settings.
+#[derive(Clone)]
+struct SettingsView {
+    adjuster: Adjustment,
+    diff_mode: ComboBoxText,
+    delta_e_adjuster: Adjustment,
+    ssim_adjuster: Adjustment,
+    bound_box: Switch,
+    color_btn: ColorButton,
+    morph_op: ComboBoxText,
+    morph_kernel: ComboBoxText,
+    morph_adjuster: Adjustment,
+    min_area_adjuster: Adjustment,
+    spot_switch: Switch,
+    align_switch: Switch,
+    frame_source: ComboBoxText,
+    stack_adjuster: Adjustment,
+    stack_method: ComboBoxText,
+}
+
+impl SettingsView {
+    /// Copies the current widget values into the application state.
+    fn read(&self, state: &mut State) {
+        let mode_id = self.diff_mode.get_active_id().map(|id| id.to_string()).unwrap_or_default();
+        let op_id = self.morph_op.get_active_id().map(|id| id.to_string()).unwrap_or_default();
+        let kernel_id = self.morph_kernel.get_active_id().map(|id| id.to_string()).unwrap_or_default();
+        let method_id = self.stack_method.get_active_id().map(|id| id.to_string()).unwrap_or_default();
+
+        let settings = &mut state.recipe.settings;
+        settings.mode = imgfx::DiffMode::from_id(&mode_id);
+        settings.significance = self.adjuster.get_value() as u8;
+        settings.delta_e = self.delta_e_adjuster.get_value() as f32;
+        settings.ssim_radius = self.ssim_adjuster.get_value() as u32;
+        settings.filter = imgfx::Filter{
+            op: imgfx::MorphOp::from_id(&op_id),
+            kernel: imgfx::Kernel::from_id(&kernel_id),
+            radius: self.morph_adjuster.get_value() as u8,
+            min_area: self.min_area_adjuster.get_value() as u32,
+        };
+        settings.align = self.align_switch.get_active();
+
+        let color = self.color_btn.get_rgba();
+        let output = &mut state.recipe.output;
+        output.bbox = self.bound_box.get_active();
+        output.marker = [to_channel(color.red), to_channel(color.green), to_channel(color.blue)];
+
+        state.spot_detect = self.spot_switch.get_active();
+        state.source_spec = self.frame_source.get_active_text()
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default();
+        state.stack_frames = self.stack_adjuster.get_value() as usize;
+        state.stack_method = stack::Method::from_id(&method_id);
+    }
+
+    /// Sets the widget values from a recipe.
+    fn write(&self, recipe: &Recipe) {
+        let settings = &recipe.settings;
+        let output = &recipe.output;
+
+        self.adjuster.set_value(settings.significance as f64);
+        self.diff_mode.set_active_id(Some(settings.mode.id()));
+        self.delta_e_adjuster.set_value(settings.delta_e as f64);
+        self.ssim_adjuster.set_value(settings.ssim_radius as f64);
+        self.morph_op.set_active_id(Some(settings.filter.op.id()));
+        self.morph_kernel.set_active_id(Some(settings.filter.kernel.id()));
+        self.morph_adjuster.set_value(settings.filter.radius as f64);
+        self.min_area_adjuster.set_value(settings.filter.min_area as f64);
+        self.align_switch.set_active(settings.align);
+        self.bound_box.set_active(output.bbox);
+
+        let color = gdk::RGBA{
+            red: output.marker[0] as f64 / 255.0,
+            green: output.marker[1] as f64 / 255.0,
+            blue: output.marker[2] as f64 / 255.0,
+            alpha: 1.0,
+        };
+        self.color_btn.set_rgba(&color);
+    }
+
+    /// Keeps the application state in sync with the settings panel widgets.
+    fn bind(&self, store: &Store) {
+        let view = self.clone();
+        let store = store.clone();
+        let sync = move || store.modify(|state| view.read(state));
+
+        sync();
+
+        let f = sync.clone();
+        self.adjuster.connect_value_changed(move |_| f());
+        let f = sync.clone();
+        self.diff_mode.connect_changed(move |_| f());
+        let f = sync.clone();
+        self.delta_e_adjuster.connect_value_changed(move |_| f());
+        let f = sync.clone();
+        self.ssim_adjuster.connect_value_changed(move |_| f());
+        let f = sync.clone();
+        self.bound_box.connect_property_active_notify(move |_| f());
+        let f = sync.clone();
+        self.color_btn.connect_color_set(move |_| f());
+        let f = sync.clone();
+        self.morph_op.connect_changed(move |_| f());
+        let f = sync.clone();
+        self.morph_kernel.connect_changed(move |_| f());
+        let f = sync.clone();
+        self.morph_adjuster.connect_value_changed(move |_| f());
+        let f = sync.clone();
+        self.min_area_adjuster.connect_value_changed(move |_| f());
+        let f = sync.clone();
+        self.spot_switch.connect_property_active_notify(move |_| f());
+        let f = sync.clone();
+        self.align_switch.connect_property_active_notify(move |_| f());
+        let f = sync.clone();
+        self.frame_source.connect_changed(move |_| f());
+        let f = sync.clone();
+        self.stack_adjuster.connect_value_changed(move |_| f());
+        self.stack_method.connect_changed(move |_| sync());
+    }
+}
+
+/// Represents the history browser widgets.
+#[derive(Clone)]
+struct HistoryView {
+    dialog: Dialog,
+    from: gtk::Entry,
+    to: gtk::Entry,
+    verdict: ComboBoxText,
+    product: ComboBoxText,
+    class: ComboBoxText,
+    list: ListStore,
+    tree: TreeView,
+}
+
+impl HistoryView {
+    /// Returns the search criteria set in the filter widgets.
+    fn filter(&self) -> Result<Filter> {
+        let date = |entry: &gtk::Entry| {
+            let text = entry.get_text().trim().to_string();
+            if text.is_empty() { Ok(None) } else { history::parse_date(&text).map(Some) }
+        };
+
+        let choice = |combo: &ComboBoxText| {
+            combo.get_active_id().map(|id| id.to_string()).filter(|id| id != "all")
+        };
+
+        return Ok(Filter{
+            from: date(&self.from)?,
+            to: date(&self.to)?,
+            verdict: choice(&self.verdict),
+            product: choice(&self.product),
+            class: choice(&self.class).and_then(|id| DefectClass::from_id(&id)),
+        });
+    }
+
+    /// Fills the product selector with the recorded products, keeping the selection.
+    fn list_products(&self, history: &History) -> Result<()> {
+        let active = self.product.get_active_id().map(|id| id.to_string());
+
+        self.product.remove_all();
+        self.product.append(Some("all"), "All");
+
+        for product in history.products()? {
+            self.product.append(Some(product.as_str()), &product);
+        }
+
+        let found = self.product.set_active_id(active.as_deref());
+        if found == false {
+            self.product.set_active_id(Some("all"));
+        }
+
+        return Ok(());
+    }
+
+    /// Lists the recorded inspections matching the filter widgets.
+    fn fill(&self, history: &History) -> Result<()> {
+        let entries = history.search(&self.filter()?)?;
+
+        self.list.clear();
+
+        for entry in entries.iter() {
+            let time = entry.local_time();
+            let rate = format!("{:.4}%", entry.defect_rate);
+
+            self.list.insert_with_values(
+                None,
+                &[0, 1, 2, 3, 4, 5, 6, 7],
+                &[
+                    &entry.id, &time, &entry.product, &entry.capture,
+                    &entry.defect_count, &rate, &entry.verdict, &entry.defect_rate,
+                ],
+            );
+        }
+
+        return Ok(());
+    }
+
+    /// Returns the id of the selected inspection.
+    fn selected(&self) -> Option<i64> {
+        let (model, iter) = self.tree.get_selection().get_selected()?;
+        return model.get_value(&iter, 0).get_some::<i64>().ok();
+    }
+}
+
+/// Represents the batch dialog widgets.
+#[derive(Clone)]
+struct BatchView {
+    dialog: Dialog,
+    dir: FileChooserButton,
+    reference: ComboBoxText,
+    mapping: FileChooserButton,
+    start_btn: Button,
+    progress: ProgressBar,
+    list: ListStore,
+}
+
+impl BatchView {
+    /// Returns the references selected in the dialog, or none for hotspot detection.
+    fn references(&self, state: &State) -> Result<Option<References>> {
+        if state.spot_detect == true {
+            return Ok(None);
+        }
+
+        let mapped = self.reference.get_active_id().map(|id| id == "mapping").unwrap_or(false);
+
+        if mapped == true {
+            let path = self.mapping.get_filename()
+                .ok_or(Error::Missing(String::from("reference mapping file")))?;
+            return Ok(Some(References::open(&path.to_string_lossy())?));
+        }
+
+        // compare against the original file, since the loaded image is resized
+        let reference = state.reference.as_ref()
+            .ok_or(Error::Missing(String::from("reference image")))?;
+
+        return Ok(Some(References::Single(reference.source.clone())));
+    }
+
+    /// Appends the result of a capture to the summary table.
+    fn add_row(&self, file: &str, result: &Result<(Report, Option<String>)>) {
+        let (defects, rate, verdict, error) = match result {
+            Ok((report, warning)) => (
+                report.defect_count.to_string(),
+                format!("{:.4}%", report.defect_rate),
+                report.verdict.name().to_string(),
+                warning.clone().unwrap_or_default(),
+            ),
+            Err(err) => (String::from("-"), String::from("-"), String::from("ERROR"), err.to_string()),
+        };
+
+        let file = file.to_string();
+        self.list.insert_with_values(None, &[0, 1, 2, 3, 4], &[&file, &defects, &rate, &verdict, &error]);
+    }
+
+    /// Shows the verdict counts of a finished batch and saves its summary.
+    fn finish(&self, summary: &Summary, output: &str) {
+        let mut msg = format!(
+            "Done: {} PASS, {} REVIEW, {} FAIL, {} errors",
+            summary.count(Verdict::Pass), summary.count(Verdict::Review),
+            summary.count(Verdict::Fail), summary.failures.len(),
+        );
+
+        match summary.save_csv(output) {
+            Ok(()) => msg = format!("{}  (summary saved to {})", msg, output),
+            Err(err) => msg = format!("{}  (summary not saved: {})", msg, err),
+        }
+
+        self.progress.set_text(Some(msg.as_str()));
+        self.start_btn.set_sensitive(true);
+    }
+}
+
 /// Builds GTK UI from Glade design.
 pub fn build(application: &gtk::Application) {
     let glade_src = include_str!("ui/ui.glade");
-    let builder = Builder::new_from_string(glade_src);
+    let builder = Builder::from_string(glade_src);
 
     // Application window
     let window: ApplicationWindow = builder
@@ -352,7 +1596,7 @@ pub fn build(application: &gtk::Application) {
         .expect("Couldn't get application window!");
 
     // Container box
-    let root_box: Box = builder
+    let _root_box: Box = builder
         .get_object("RootBox")
         .expect("Couldn't get rootbox widget!");
 
@@ -377,6 +1621,18 @@ pub fn build(application: &gtk::Application) {
         .get_object("DiffBtn")
         .expect("Couldn't get diff button widget!");
 
+    let report_btn: Button = builder
+        .get_object("ReportBtn")
+        .expect("Couldn't get report button widget!");
+
+    let history_btn: Button = builder
+        .get_object("HistoryBtn")
+        .expect("Couldn't get history button widget!");
+
+    let batch_btn: Button = builder
+        .get_object("BatchBtn")
+        .expect("Couldn't get batch button widget!");
+
     let zoom_in_btn: Button = builder
         .get_object("ZoomInBtn")
         .expect("Couldn't get zoom-in button widget!");
@@ -401,16 +1657,20 @@ pub fn build(application: &gtk::Application) {
         .get_object("FullscreenBtn")
         .expect("Couldn't get fullscreen button widget!");
     
-    let key = String::from("fullscreen");
-    let val = String::from("off");
-    aux::set_env(&key, &val);
-    
     let quit_btn: Button = builder
         .get_object("QuitBtn")
         .expect("Couldn't get quit button widget!");
 
     // Settings panel widgets
-    let slider: Scale = builder
+    let recipe_combo: ComboBoxText = builder
+        .get_object("Recipe")
+        .expect("Couldn't get recipe widget!");
+
+    let recipe_save_btn: Button = builder
+        .get_object("RecipeSaveBtn")
+        .expect("Couldn't get recipe save button widget!");
+
+    let _slider: Scale = builder
         .get_object("Slider")
         .expect("Couldn't get significance slider widget!");
 
@@ -418,18 +1678,90 @@ pub fn build(application: &gtk::Application) {
         .get_object("Adjuster")
         .expect("Couldn't get adjustment widget!");
 
-    // set optimum defect significance
-    let value: f64 = 10.0;
-    adjuster.set_value(value);
+    let diff_mode: ComboBoxText = builder
+        .get_object("DiffMode")
+        .expect("Couldn't get diff mode widget!");
+    
+    let delta_e_adjuster: Adjustment = builder
+        .get_object("DeltaEAdjuster")
+        .expect("Couldn't get color difference adjustment widget!");
+    
+    let ssim_adjuster: Adjustment = builder
+        .get_object("SsimAdjuster")
+        .expect("Couldn't get ssim radius adjustment widget!");
     
     let bound_box: Switch = builder
         .get_object("BoundingBox")
         .expect("Couldn't get switch widget!");
 
+    let morph_op: ComboBoxText = builder
+        .get_object("MorphOp")
+        .expect("Couldn't get noise filter widget!");
+
+    let morph_kernel: ComboBoxText = builder
+        .get_object("MorphKernel")
+        .expect("Couldn't get structuring element widget!");
+
+    let morph_adjuster: Adjustment = builder
+        .get_object("MorphAdjuster")
+        .expect("Couldn't get filter radius adjustment widget!");
+
+    let min_area_adjuster: Adjustment = builder
+        .get_object("MinAreaAdjuster")
+        .expect("Couldn't get minimum area adjustment widget!");
+
+    let spot_switch: Switch = builder
+        .get_object("SpotDetect")
+        .expect("Couldn't get switch widget!");
+
+    let align_switch: Switch = builder
+        .get_object("AutoAlign")
+        .expect("Couldn't get switch widget!");
+
+    let roi_tool: ComboBoxText = builder
+        .get_object("RoiTool")
+        .expect("Couldn't get roi tool widget!");
+
+    let roi_mask_btn: Button = builder
+        .get_object("RoiMaskBtn")
+        .expect("Couldn't get roi mask button widget!");
+
+    let roi_clear_btn: Button = builder
+        .get_object("RoiClearBtn")
+        .expect("Couldn't get roi clear button widget!");
+
     let color_btn: ColorButton = builder
         .get_object("MarkerColor")
         .expect("Couldn't get color picker widget!");
 
+    let frame_source: ComboBoxText = builder
+        .get_object("FrameSource")
+        .expect("Couldn't get frame source widget!");
+
+    let stack_adjuster: Adjustment = builder
+        .get_object("StackAdjuster")
+        .expect("Couldn't get frames to merge adjustment widget!");
+
+    let stack_method: ComboBoxText = builder
+        .get_object("StackMethod")
+        .expect("Couldn't get merge method widget!");
+
+    let hot_folder: FileChooserButton = builder
+        .get_object("HotFolder")
+        .expect("Couldn't get hot folder widget!");
+
+    let hot_folder_results: ComboBoxText = builder
+        .get_object("HotFolderResults")
+        .expect("Couldn't get hot folder results widget!");
+
+    let hot_folder_watch: Switch = builder
+        .get_object("HotFolderWatch")
+        .expect("Couldn't get switch widget!");
+
+    let live_switch: Switch = builder
+        .get_object("LiveSwitch")
+        .expect("Couldn't get switch widget!");
+
     // Image view widgets
     let top_ruler: DrawingArea = builder
         .get_object("TopRuler")
@@ -455,6 +1787,72 @@ pub fn build(application: &gtk::Application) {
         .get_object("CaptImage")
         .expect("Couldn't get captured image widget!");
 
+    // History browser widgets
+    let history_dialog: Dialog = builder
+        .get_object("HistoryDialog")
+        .expect("Couldn't get history dialog widget!");
+
+    let history_from: gtk::Entry = builder
+        .get_object("HistoryFrom")
+        .expect("Couldn't get history date widget!");
+
+    let history_to: gtk::Entry = builder
+        .get_object("HistoryTo")
+        .expect("Couldn't get history date widget!");
+
+    let history_verdict: ComboBoxText = builder
+        .get_object("HistoryVerdict")
+        .expect("Couldn't get history verdict widget!");
+
+    let history_product: ComboBoxText = builder
+        .get_object("HistoryProduct")
+        .expect("Couldn't get history product widget!");
+
+    let history_class: ComboBoxText = builder
+        .get_object("HistoryClass")
+        .expect("Couldn't get history class widget!");
+
+    let history_store: ListStore = builder
+        .get_object("HistoryStore")
+        .expect("Couldn't get history list store!");
+
+    let history_tree: TreeView = builder
+        .get_object("HistoryView")
+        .expect("Couldn't get history view widget!");
+
+    let history_search_btn: Button = builder
+        .get_object("HistorySearchBtn")
+        .expect("Couldn't get history search button widget!");
+
+    // Batch dialog widgets
+    let batch_dialog: Dialog = builder
+        .get_object("BatchDialog")
+        .expect("Couldn't get batch dialog widget!");
+
+    let batch_dir: FileChooserButton = builder
+        .get_object("BatchDir")
+        .expect("Couldn't get batch directory widget!");
+
+    let batch_reference: ComboBoxText = builder
+        .get_object("BatchReference")
+        .expect("Couldn't get batch reference widget!");
+
+    let batch_mapping: FileChooserButton = builder
+        .get_object("BatchMapping")
+        .expect("Couldn't get batch mapping widget!");
+
+    let batch_start_btn: Button = builder
+        .get_object("BatchStartBtn")
+        .expect("Couldn't get batch start button widget!");
+
+    let batch_progress: ProgressBar = builder
+        .get_object("BatchProgress")
+        .expect("Couldn't get batch progress widget!");
+
+    let batch_store: ListStore = builder
+        .get_object("BatchStore")
+        .expect("Couldn't get batch list store!");
+
     // Info panel widget
     let info_panel: InfoBar = builder
         .get_object("InfoPanel")
@@ -464,6 +1862,10 @@ pub fn build(application: &gtk::Application) {
         .get_object("DefectInfo")
         .expect("Couldn't get defect info label widget!");
     
+    let align_info: Label = builder
+        .get_object("AlignInfo")
+        .expect("Couldn't get align info label widget!");
+    
     let position_info: Label = builder
         .get_object("PositionInfo")
         .expect("Couldn't get position info label widget!");
@@ -471,115 +1873,316 @@ pub fn build(application: &gtk::Application) {
     //--------------------------------------------------------------------------
     // event handlers
 
+    // color info panel by verdict
+    let provider = CssProvider::new();
+    provider.load_from_data(VERDICT_CSS.as_bytes()).expect("Couldn't load verdict styles!");
+
+    if let Some(screen) = gdk::Screen::get_default() {
+        StyleContext::add_provider_for_screen(&screen, &provider, gtk::STYLE_PROVIDER_PRIORITY_APPLICATION);
+    }
+
     window.set_default_size(1280, 800);
     window.maximize();
-    window.set_application(application);
+    window.set_application(Some(application));
     window.connect_delete_event(quit);
     
-    let win_img = window.clone();
-    let win_cam = window.clone();
-    let win_full = window.clone();
-    let win_quit = window.clone();
-
-    let mut ref_img1 = Image::new();
-    ref_img1.clone_from(&ref_img);
-
-    let mut ref_img2 = Image::new();
-    ref_img2.clone_from(&ref_img);
-
-    let mut ref_img3 = Image::new();
-    ref_img3.clone_from(&ref_img);
-
-    let mut ref_img4 = Image::new();
-    ref_img4.clone_from(&ref_img);
-
-    let mut ref_img5 = Image::new();
-    ref_img5.clone_from(&ref_img);
+    let store = Store::new();
+
+    let settings_view = SettingsView{
+        adjuster: adjuster,
+        diff_mode: diff_mode,
+        delta_e_adjuster: delta_e_adjuster,
+        ssim_adjuster: ssim_adjuster,
+        bound_box: bound_box,
+        color_btn: color_btn,
+        morph_op: morph_op,
+        morph_kernel: morph_kernel,
+        morph_adjuster: morph_adjuster,
+        min_area_adjuster: min_area_adjuster,
+        spot_switch: spot_switch,
+        align_switch: align_switch,
+        frame_source: frame_source,
+        stack_adjuster: stack_adjuster,
+        stack_method: stack_method,
+    };
+
+    // start with default settings until a recipe is selected
+    settings_view.write(&store.get().recipe);
+    settings_view.bind(&store);
+    list_recipes(&recipe_combo);
+
+    // widgets observing the application state
+    let (ref_view, capt_view) = (ref_img.clone(), capt_img.clone());
+    store.connect_changed(move |state| {
+        render_view(&ref_view, state, Pane::Reference);
+        render_view(&capt_view, state, Pane::Capture);
+    });
 
-    let mut capt_img1 = Image::new();
-    capt_img1.clone_from(&capt_img);
+    let (info_view, defect_view) = (info_panel.clone(), defect_info.clone());
+    store.connect_changed(move |state| render_info(&info_view, &defect_view, &align_info, state));
 
-    let mut capt_img2 = Image::new();
-    capt_img2.clone_from(&capt_img);
+    let win_view = window.clone();
+    store.connect_changed(move |state| {
+        if state.fullscreen == true {
+            win_view.fullscreen();
+        } else {
+            win_view.unfullscreen();
+        }
+    });
 
-    let mut capt_img3 = Image::new();
-    capt_img3.clone_from(&capt_img);
+    let win_img = window.clone();
+    let win_cam = window.clone();
+    let win_quit = window.clone();
 
-    let mut capt_img4 = Image::new();
-    capt_img4.clone_from(&capt_img);
+    let store_img = store.clone();
+    let store_cam = store.clone();
+    let store_diff = store.clone();
+    let store_in = store.clone();
+    let store_out = store.clone();
+    let store_fit = store.clone();
+    let store_full = store.clone();
 
-    let mut capt_img5 = Image::new();
-    capt_img5.clone_from(&capt_img);
-    
     let info_panel1 = info_panel.clone();
-    let info_panel2 = info_panel.clone();
     let pos_info = position_info.clone();
     
     top_ruler.connect_draw(draw_top_ruler);
     right_ruler.connect_draw(draw_right_ruler);
     
+    let store_press = store.clone();
+    let store_release = store.clone();
+    let ref_img1 = ref_img.clone();
+    let ref_img2 = ref_img.clone();
+
     ref_evt.connect_button_press_event(move |_, evt_btn| {
         let (xp, yp) = evt_btn.get_position();
         display_position(&info_panel1, &pos_info, xp as i32, yp as i32);
+        roi_pressed(&store_press, &ref_img1, evt_btn);
+        Inhibit(false)
+    });
+
+    ref_evt.connect_button_release_event(move |_, evt_btn| {
+        roi_released(&store_release, &ref_img2, evt_btn);
         Inhibit(false)
     });
     
     capt_evt.connect_button_press_event(move |_, evt_btn| {
         let (xp, yp) = evt_btn.get_position();
-        display_position(&info_panel2, &position_info, xp as i32, yp as i32);
+        display_position(&info_panel, &position_info, xp as i32, yp as i32);
         Inhibit(false)
     });
     
     image_btn.connect_clicked(move |_| {
-        let ref_path = image_btn_clicked(&win_img, &ref_img1);
-        let key = String::from("ref_path");
-        let val = ref_path;
-        aux::set_env(&key, &val);
+        match image_btn_clicked(&win_img, None) {
+            Ok(Some(loaded)) => store_img.update(|state| {
+                state.reference = Some(loaded);
+                state.outcome = None;
+                state.error = None;
+            }),
+            Ok(None) => {}
+            Err(err) => store_img.update(|state| state.error = Some(err)),
+        }
     });
 
     camera_btn.connect_clicked(move |_| {
-        let capt_path = image_btn_clicked(&win_cam, &capt_img1);
-        let key = String::from("capt_path");
-        let val = capt_path;
-        aux::set_env(&key, &val);
-        let active = String::from("active");
-        aux::set_env(&active, &val);
+        match camera_btn_clicked(&win_cam, &store_cam) {
+            Ok(Some(loaded)) => store_cam.update(|state| {
+                state.capture = Some(loaded);
+                state.outcome = None;
+                state.error = None;
+            }),
+            Ok(None) => {}
+            Err(err) => store_cam.update(|state| state.error = Some(err)),
+        }
     });
 
     diff_btn.connect_clicked(move |_| {
-        let significance = adjuster.get_value() as u8;
-        let bound_state = bound_box.get_state();
+        let result = {
+            let state = store_diff.get();
+
+            if state.spot_detect == true {
+                detect_spots(&state)
+            } else {
+                diff_btn_clicked(&state)
+            }
+        };
         
-        zoom_fit_btn_clicked(&ref_img2, &capt_img2);
-        
-        diff_btn_clicked(
-            &info_panel,
-            &defect_info,
-            &ref_img2,
-            &capt_img2,
-            significance,
-            bound_state,
-        );
+        store_diff.update(|state| {
+            state.zoom = 1.0;
+
+            match result {
+                Ok(outcome) => {
+                    state.outcome = Some(outcome);
+                    state.error = None;
+                }
+                Err(err) => state.error = Some(err),
+            }
+        });
     });
     
-    let zoom_width: i32 = 60;
-    let zoom_height: i32 = 80;
-    
-    zoom_in_btn.connect_clicked(move |_| zoom_in_btn_clicked(&ref_img3, &capt_img3, zoom_width, zoom_height));
+    let store_tool = store.clone();
+
+    roi_tool.connect_changed(move |combo| {
+        let tool_id = combo.get_active_id().map(|id| id.to_string()).unwrap_or_default();
+        store_tool.update(|state| {
+            state.roi_tool = RoiTool::from_id(&tool_id);
+            state.draft.clear();
+        });
+    });
+
+    let store_mask = store.clone();
+    let win_mask = window.clone();
+
+    roi_mask_btn.connect_clicked(move |_| {
+        match image_btn_clicked(&win_mask, None) {
+            Ok(Some(loaded)) => store_mask.update(|state| state.recipe.settings.roi.mask = Some(loaded.source)),
+            Ok(None) => {}
+            Err(err) => store_mask.update(|state| state.error = Some(err)),
+        }
+    });
+
+    let store_clear = store.clone();
+
+    roi_clear_btn.connect_clicked(move |_| {
+        store_clear.update(|state| {
+            state.recipe.settings.roi = Roi::default();
+            state.draft.clear();
+        });
+    });
+
+    let store_report = store.clone();
+    let win_report = window.clone();
+
+    report_btn.connect_clicked(move |_| {
+        if let Err(err) = report_btn_clicked(&win_report, &store_report) {
+            store_report.update(|state| state.error = Some(err));
+        }
+    });
+
+    let history_view = HistoryView{
+        dialog: history_dialog,
+        from: history_from,
+        to: history_to,
+        verdict: history_verdict,
+        product: history_product,
+        class: history_class,
+        list: history_store,
+        tree: history_tree,
+    };
+
+    let store_history = store.clone();
+    let view_history = history_view.clone();
+    let view_settings = settings_view.clone();
+
+    history_btn.connect_clicked(move |_| {
+        if let Err(err) = history_btn_clicked(&view_history, &view_settings, &store_history) {
+            store_history.update(|state| state.error = Some(err));
+        }
+    });
+
+    let store_search = store.clone();
+    let view_search = history_view.clone();
+
+    history_search_btn.connect_clicked(move |_| {
+        let result = History::open(&history::get_path()).and_then(|history| view_search.fill(&history));
+        if let Err(err) = result {
+            store_search.update(|state| state.error = Some(err));
+        }
+    });
+
+    // set file filter with supported mapping types
+    let mapping_filter = FileFilter::new();
+    mapping_filter.add_pattern("*.csv");
+    batch_mapping.set_filter(&mapping_filter);
+
+    let batch_view = BatchView{
+        dialog: batch_dialog,
+        dir: batch_dir,
+        reference: batch_reference,
+        mapping: batch_mapping,
+        start_btn: batch_start_btn,
+        progress: batch_progress,
+        list: batch_store,
+    };
+
+    let store_batch = store.clone();
+    let view_batch = batch_view.clone();
+
+    batch_btn.connect_clicked(move |_| batch_btn_clicked(&view_batch, &store_batch));
+
+    let store_start = store.clone();
+    let view_start = batch_view.clone();
+
+    batch_view.start_btn.connect_clicked(move |_| {
+        if let Err(err) = batch_start_btn_clicked(&view_start, &store_start) {
+            view_start.progress.set_text(Some(format!("Error: {}", err).as_str()));
+        }
+    });
+
+    // double click on a row opens the inspection
+    let dialog_open = history_view.dialog.clone();
+    history_view.tree.connect_row_activated(move |_, _, _| dialog_open.response(ResponseType::Ok));
+
+    let store_watch = store.clone();
+
+    hot_folder_watch.connect_property_active_notify(move |switch| {
+        if switch.get_active() == false {
+            hot_folder_unwatched(&store_watch);
+            return;
+        }
+
+        if let Err(err) = hot_folder_watched(&hot_folder, &hot_folder_results, &store_watch) {
+            store_watch.update(|state| state.error = Some(err));
+            switch.set_active(false);
+        }
+    });
+
+    let store_live = store.clone();
+
+    live_switch.connect_property_active_notify(move |switch| {
+        let result = if switch.get_active() == true {
+            live_started(switch, &store_live)
+        } else {
+            live_stopped(&store_live)
+        };
+
+        if let Err(err) = result {
+            store_live.update(|state| state.error = Some(err));
+            switch.set_active(false);
+        }
+    });
+
+    let store_recipe = store.clone();
+    let view_recipe = settings_view.clone();
+
+    recipe_combo.connect_changed(move |combo| {
+        if let Err(err) = recipe_changed(combo, &view_recipe, &store_recipe) {
+            store_recipe.update(|state| state.error = Some(err));
+        }
+    });
+
+    let store_save = store.clone();
+    let win_save = window.clone();
+
+    recipe_save_btn.connect_clicked(move |_| {
+        if let Err(err) = recipe_save_btn_clicked(&win_save, &recipe_combo, &store_save) {
+            store_save.update(|state| state.error = Some(err));
+        }
+    });
+
+    zoom_in_btn.connect_clicked(move |_| zoom_in_btn_clicked(&store_in));
 
-    zoom_out_btn.connect_clicked(move |_| zoom_out_btn_clicked(&ref_img4, &capt_img4, zoom_width, zoom_height));
+    zoom_out_btn.connect_clicked(move |_| zoom_out_btn_clicked(&store_out));
 
-    zoom_fit_btn.connect_clicked(move |_| zoom_fit_btn_clicked(&ref_img5, &capt_img5));
+    zoom_fit_btn.connect_clicked(move |_| zoom_fit_btn_clicked(&store_fit));
 
     config_btn.connect_clicked(move |_| config_btn_clicked(&settings_panel));
 
     about_btn.connect_clicked(move |_| about_btn_clicked());
     
-    fullscreen_btn.connect_clicked(move |btn| fullscreen_btn_clicked(&btn, &win_full));
+    fullscreen_btn.connect_clicked(move |_| fullscreen_btn_clicked(&store_full));
     
     quit_btn.connect_clicked(move |_| {
-        win_quit.destroy();
-        Inhibit(false);
+        win_quit.close();
     });
     
     window.show_all();
@@ -0,0 +1,348 @@
+//! Application state module for Glassvis.
+
+use std::cell::{Ref, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::time::Instant;
+
+use glassvis::{
+    source, stack, Batch, Error, FrameSource, HotFolder, Inspection, Recipe, Report, Result, Settings, Shape, Summary,
+};
+use glassvis::report::Input;
+use glib::SourceId;
+use image::{DynamicImage, GenericImageView};
+
+/// Smallest allowed zoom factor.
+pub const MIN_ZOOM: f64 = 0.1;
+
+/// Zoom factor change of a single zoom-in/zoom-out step.
+pub const ZOOM_STEP: f64 = 0.1;
+
+/// Represents the image view pane that shows the latest result.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pane {
+    Reference,
+    Capture,
+}
+
+/// Represents the tools for drawing region-of-interest shapes over the reference image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RoiTool {
+    None,
+    IncludeRect,
+    ExcludeRect,
+    IncludePolygon,
+    ExcludePolygon,
+}
+
+impl RoiTool {
+    /// Parses a drawing tool from its settings panel identifier.
+    pub fn from_id(id: &str) -> RoiTool {
+        let tool = match id {
+            "include-rect"    => RoiTool::IncludeRect,
+            "exclude-rect"    => RoiTool::ExcludeRect,
+            "include-polygon" => RoiTool::IncludePolygon,
+            "exclude-polygon" => RoiTool::ExcludePolygon,
+            _                 => RoiTool::None,
+        };
+
+        return tool;
+    }
+
+    /// Checks if the tool draws exclusion shapes.
+    pub fn excludes(&self) -> bool {
+        return *self == RoiTool::ExcludeRect || *self == RoiTool::ExcludePolygon;
+    }
+}
+
+/// Represents a loaded image along with its processed file path.
+pub struct Loaded {
+    pub path: String,
+    /// Original image file the processed file was made from.
+    pub source: String,
+    pub image: DynamicImage,
+    /// Dimensions of the original image, which recipe shapes refer to.
+    pub original: (u32, u32),
+}
+
+impl Loaded {
+    /// Returns the scale factors from original image coordinates to the processed image.
+    pub fn scale(&self) -> (f64, f64) {
+        let (width, height) = self.image.dimensions();
+        let (orig_width, orig_height) = self.original;
+
+        if orig_width == 0 || orig_height == 0 {
+            return (1.0, 1.0);
+        }
+        return (width as f64 / orig_width as f64, height as f64 / orig_height as f64);
+    }
+}
+
+/// Represents the result of the latest inspection.
+pub struct Outcome {
+    pub inspection: Inspection,
+    pub image: DynamicImage,
+    pub pane: Pane,
+    /// Settings the inspection was run with.
+    pub settings: Settings,
+    /// Number of bright and dark spots (hotspot detection only).
+    pub polarity: Option<(u32, u32)>,
+    /// Machine-readable result, saved next to the marked image.
+    pub report: Report,
+    /// Reason the result could not be recorded in the history database.
+    pub warning: Option<String>,
+}
+
+/// Represents an open frame source along with the spec it was opened from.
+pub struct Grabber {
+    pub spec: String,
+    pub source: Box<dyn FrameSource>,
+}
+
+/// Represents the hot folder being watched.
+pub struct Watch {
+    pub folder: HotFolder,
+    /// Writes results next to the input files instead of "data/output" directory.
+    pub beside_input: bool,
+    /// Timer polling the folder.
+    pub timer: SourceId,
+}
+
+/// Represents a batch being inspected from the batch dialog.
+pub struct BatchRun {
+    pub batch: Batch,
+    /// Index of the next capture to inspect.
+    pub next: usize,
+    pub summary: Summary,
+    /// CSV file the summary is saved to once the batch is done.
+    pub output: String,
+}
+
+/// Represents a frame inspection handed to the live inspection worker thread.
+pub struct LiveJob {
+    /// Frame source to grab from, handed back along with the result.
+    pub grabber: Grabber,
+    /// Reference image along with its file description (none for hotspot detection).
+    pub reference: Option<(DynamicImage, Input)>,
+    pub recipe: Recipe,
+}
+
+/// Represents a finished live frame inspection.
+pub struct LiveDone {
+    pub grabber: Grabber,
+    /// Inspected frame along with the result.
+    pub result: Result<(Loaded, Outcome)>,
+}
+
+/// Represents a running live inspection.
+pub struct Live {
+    /// Timer handing the frames to the worker thread.
+    pub timer: SourceId,
+    /// Queue of the worker thread, which stops once the queue is dropped.
+    pub jobs: Sender<LiveJob>,
+    /// Whether a frame is being inspected (further frames are skipped meanwhile).
+    pub busy: bool,
+    /// Number of inspected frames.
+    pub frames: u32,
+    pub started: Instant,
+    /// Reference image file of the latest frame.
+    reference: Option<Input>,
+}
+
+impl Live {
+    /// Creates a live inspection driven by a timer, inspecting frames on a worker thread.
+    pub fn new(timer: SourceId, jobs: Sender<LiveJob>) -> Live {
+        return Live{
+            timer: timer,
+            jobs: jobs,
+            busy: false,
+            frames: 0,
+            started: Instant::now(),
+            reference: None,
+        };
+    }
+
+    /// Returns the frame rate since the start in frames per second.
+    pub fn rate(&self) -> f64 {
+        let secs = self.started.elapsed().as_secs_f64();
+
+        if secs <= 0.0 {
+            return 0.0;
+        }
+        return self.frames as f64 / secs;
+    }
+
+    /// Describes a reference image file, hashing it only when it changed.
+    pub fn reference(&mut self, path: &str) -> Result<Input> {
+        let stale = match &self.reference {
+            Some(input) => input.path != path,
+            None => true,
+        };
+
+        if stale {
+            self.reference = Some(Input::from_file(path)?);
+        }
+
+        return self.reference.clone().ok_or(Error::Missing(format!("reference image at '{}'", path)));
+    }
+}
+
+/// Represents the whole application state.
+pub struct State {
+    pub reference: Option<Loaded>,
+    pub capture: Option<Loaded>,
+    pub outcome: Option<Outcome>,
+    pub error: Option<Error>,
+    /// Recipe holding the current settings and output options.
+    pub recipe: Recipe,
+    pub spot_detect: bool,
+    pub roi_tool: RoiTool,
+    /// Vertices of the region-of-interest shape being drawn (original image coordinates).
+    pub draft: Vec<[i32; 2]>,
+    pub zoom: f64,
+    pub fullscreen: bool,
+    /// Frame source spec selected in settings panel ("dialog" picks an image file).
+    pub source_spec: String,
+    /// Number of consecutive frames merged into one capture.
+    pub stack_frames: usize,
+    /// Method merging grabbed frames or selected image files.
+    pub stack_method: stack::Method,
+    /// Frame source kept open between grabs.
+    pub grabber: Option<Grabber>,
+    pub watch: Option<Watch>,
+    pub batch: Option<BatchRun>,
+    pub live: Option<Live>,
+}
+
+impl State {
+    /// Adds a finished region-of-interest shape drawn with the current tool.
+    pub fn add_shape(&mut self, shape: Shape) {
+        let roi = &mut self.recipe.settings.roi;
+
+        if self.roi_tool.excludes() {
+            roi.exclude.push(shape);
+        } else {
+            roi.include.push(shape);
+        }
+    }
+
+    /// Returns the selected frame source, reopening it if the selection changed.
+    fn source(&mut self) -> Result<&mut dyn FrameSource> {
+        let stale = match &self.grabber {
+            Some(grabber) => grabber.spec != self.source_spec,
+            None => true,
+        };
+
+        if stale {
+            // release the previous device before opening another one
+            self.grabber = None;
+
+            self.grabber = Some(Grabber{
+                spec: self.source_spec.clone(),
+                source: source::open(&self.source_spec)?,
+            });
+        }
+
+        match &mut self.grabber {
+            Some(grabber) => return Ok(grabber.source.as_mut()),
+            None => return Err(Error::Missing(String::from("frame source"))),
+        }
+    }
+
+    /// Takes the selected frame source out of the state (opening it if necessary),
+    /// e.g. to hand it to a worker thread.
+    pub fn take_grabber(&mut self) -> Result<Grabber> {
+        self.source()?;
+        return self.grabber.take().ok_or(Error::Missing(String::from("frame source")));
+    }
+
+    /// Grabs the selected number of consecutive frames and merges them into one capture.
+    pub fn grab_merged(&mut self) -> Result<DynamicImage> {
+        let (count, method) = (self.stack_frames, self.stack_method);
+        return stack::grab(self.source()?, count, method);
+    }
+
+    /// Returns the image shown in the given pane.
+    pub fn shown(&self, pane: Pane) -> Option<&DynamicImage> {
+        if let Some(outcome) = &self.outcome {
+            if outcome.pane == pane {
+                return Some(&outcome.image);
+            }
+        }
+
+        let loaded = match pane {
+            Pane::Reference => self.reference.as_ref(),
+            Pane::Capture   => self.capture.as_ref(),
+        };
+
+        return loaded.map(|loaded| &loaded.image);
+    }
+}
+
+/// Callback run with the application state after every change.
+type Observer = Box<dyn Fn(&State)>;
+
+/// Shared handle to the application state that notifies observers of changes.
+#[derive(Clone)]
+pub struct Store {
+    state: Rc<RefCell<State>>,
+    observers: Rc<RefCell<Vec<Observer>>>,
+}
+
+impl Store {
+    /// Creates a store holding the initial application state.
+    pub fn new() -> Store {
+        let state = State{
+            reference: None,
+            capture: None,
+            outcome: None,
+            error: None,
+            recipe: Recipe::default(),
+            spot_detect: false,
+            roi_tool: RoiTool::None,
+            draft: Vec::new(),
+            zoom: 1.0,
+            fullscreen: false,
+            source_spec: String::from("dialog"),
+            stack_frames: 1,
+            stack_method: stack::Method::Mean,
+            grabber: None,
+            watch: None,
+            batch: None,
+            live: None,
+        };
+
+        return Store{
+            state: Rc::new(RefCell::new(state)),
+            observers: Rc::new(RefCell::new(Vec::new())),
+        };
+    }
+
+    /// Registers an observer called after every notifying update.
+    /// Observers must not update the store themselves.
+    pub fn connect_changed<F: Fn(&State) + 'static>(&self, observer: F) {
+        self.observers.borrow_mut().push(Box::new(observer));
+    }
+
+    /// Borrows the current state.
+    pub fn get(&self) -> Ref<'_, State> {
+        return self.state.borrow();
+    }
+
+    /// Modifies the state and notifies all observers.
+    pub fn update<F: FnOnce(&mut State)>(&self, change: F) {
+        self.modify(change);
+
+        let state = self.state.borrow();
+        for observer in self.observers.borrow().iter() {
+            observer(&state);
+        }
+    }
+
+    /// Modifies the state without notifying observers (e.g. to mirror widget values).
+    /// Returns the result of the change.
+    pub fn modify<T, F: FnOnce(&mut State) -> T>(&self, change: F) -> T {
+        let mut state = self.state.borrow_mut();
+        return change(&mut state);
+    }
+}